@@ -23,6 +23,14 @@ struct Args {
 
     #[arg(short, long)]
     brute_force: bool,
+
+    /// Stop recording neighbors of a particle once it has this many.
+    #[arg(long)]
+    max_neighbors: Option<usize>,
+
+    /// Write the pairs that are only neighbors through the periodic boundary to this file.
+    #[arg(long)]
+    boundary_output: Option<String>,
 }
 
 fn main() {
@@ -43,6 +51,7 @@ fn main() {
                 interaction_radius: input.interaction_radius,
                 space_width: input.space_length,
                 space_height: input.space_length,
+                max_neighbors: args.max_neighbors,
             },
         )
     } else {
@@ -55,14 +64,24 @@ fn main() {
                 space_height: input.space_length,
                 columns: input.grid_size,
                 rows: input.grid_size,
+                max_neighbors: args.max_neighbors,
             },
         )
     };
     let end = Instant::now();
 
+    if output.is_truncated() {
+        eprintln!("Some particles have more than the maximum amount of neighbors, the output is truncated.");
+    }
+
     if let Some(output_file) = args.output {
         fs::write(output_file, format!("{output}")).unwrap();
     }
+    if let Some(boundary_file) = args.boundary_output {
+        let boundary_pairs =
+            output.boundary_crossing_pairs(&input.particles, input.interaction_radius);
+        fs::write(boundary_file, format!("{boundary_pairs}")).unwrap();
+    }
     let delta = (end - start).as_secs_f64();
     eprintln!("{delta}");
 }
@@ -0,0 +1,125 @@
+use std::time::Instant;
+
+use cim::{
+    cim_finder::CimNeighborFinder,
+    neighbor_finder::NeighborFinder,
+    simple_finder::{self, SimpleNeighborFinder},
+};
+use clap::Parser as _parser;
+use tp1::generator::generate;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Finder {
+    Cim,
+    BruteForce,
+}
+
+/// Runs a neighbor finder on a generated dataset and prints how long it took.
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, value_enum, default_value_t = Finder::Cim)]
+    finder: Finder,
+
+    /// Expected amount of particles, the actual amount follows a Poisson distribution.
+    #[arg(short, long, default_value_t = 1000)]
+    particles: usize,
+
+    /// Particles per unit of area.
+    #[arg(short, long, default_value_t = 1.0)]
+    density: f64,
+
+    #[arg(long, default_value_t = 0.25)]
+    radius: f64,
+
+    #[arg(long, default_value_t = 1.0)]
+    interaction_radius: f64,
+
+    #[arg(short, long)]
+    cyclic: bool,
+
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+}
+
+struct Bench {
+    particles: usize,
+    columns: usize,
+    rows: usize,
+    time: f64,
+    pairs: usize,
+}
+
+fn bench(args: &Args) -> Bench {
+    let space_length = (args.particles as f64 / args.density).sqrt();
+    let (particles, system_info) = generate(
+        args.seed,
+        args.density,
+        space_length,
+        args.radius,
+        args.interaction_radius,
+        args.cyclic,
+    );
+
+    let (columns, rows) = (system_info.columns, system_info.rows);
+
+    let start = Instant::now();
+    let neighbors = match args.finder {
+        Finder::Cim => CimNeighborFinder::find_neighbors(&particles, system_info),
+        Finder::BruteForce => SimpleNeighborFinder::find_neighbors(
+            &particles,
+            simple_finder::SystemInfo {
+                cyclic: system_info.cyclic,
+                interaction_radius: system_info.interaction_radius,
+                space_width: system_info.space_width,
+                space_height: system_info.space_height,
+                max_neighbors: None,
+            },
+        ),
+    };
+    let time = (Instant::now() - start).as_secs_f64();
+
+    Bench {
+        particles: particles.len(),
+        columns,
+        rows,
+        time,
+        pairs: neighbors.pair_count(),
+    }
+}
+
+fn main() {
+    let Bench {
+        particles,
+        columns,
+        rows,
+        time,
+        pairs,
+    } = bench(&Args::parse());
+
+    println!("particles: {particles}");
+    println!("grid: {columns}x{rows}");
+    println!("time: {time}");
+    println!("pairs: {pairs}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_dataset_gives_sane_numbers() {
+        let args =
+            |finder| Args::parse_from(["finder-bench", "--particles", "50", "--finder", finder]);
+
+        let cim = bench(&args("cim"));
+        assert!(cim.particles > 0);
+        assert!(cim.columns > 0 && cim.rows > 0);
+        assert!(cim.time >= 0.0);
+        assert!(cim.pairs <= cim.particles * (cim.particles - 1) / 2);
+
+        let brute_force = bench(&args("brute-force"));
+        assert_eq!(brute_force.particles, cim.particles);
+        assert_eq!(brute_force.pairs, cim.pairs);
+    }
+}
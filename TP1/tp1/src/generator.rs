@@ -0,0 +1,88 @@
+use cim::cim_finder::SystemInfo;
+use nalgebra::Vector2;
+use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+
+use crate::particle::Particle;
+
+/// Draws the amount of events of a unit rate Poisson process in the interval `[0, lambda)`.
+fn poisson<R: Rng>(rng: &mut R, lambda: f64) -> usize {
+    let mut count = 0;
+    let mut t = -(1.0 - rng.gen::<f64>()).ln();
+    while t < lambda {
+        count += 1;
+        t -= (1.0 - rng.gen::<f64>()).ln();
+    }
+    count
+}
+
+/// Generates a Poisson point process with `density` particles per unit of area in a square space
+/// of side `space_length`, along with the `SystemInfo` to run the CIM on it. The grid uses the
+/// largest amount of cells that still makes them wider than `interaction_radius + 2 * radius`.
+pub fn generate(
+    seed: u64,
+    density: f64,
+    space_length: f64,
+    radius: f64,
+    interaction_radius: f64,
+    cyclic: bool,
+) -> (Vec<Particle>, SystemInfo) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let count = poisson(&mut rng, density * space_length.powi(2));
+    let coordinate = Uniform::new(0.0, space_length);
+
+    let particles = (0..count)
+        .map(|id| Particle {
+            id,
+            position: Vector2::new(rng.sample(coordinate), rng.sample(coordinate)),
+            radius,
+        })
+        .collect();
+
+    let m = ((space_length / (interaction_radius + 2.0 * radius)).floor() as usize).max(1);
+
+    (
+        particles,
+        SystemInfo {
+            cyclic,
+            interaction_radius,
+            space_width: space_length,
+            space_height: space_length,
+            columns: m,
+            rows: m,
+            max_neighbors: None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_is_close_to_the_requested_one() {
+        let (density, space_length) = (2.0, 100.0);
+        for seed in 0..5 {
+            let (particles, _) = generate(seed, density, space_length, 0.1, 1.0, false);
+
+            let generated = particles.len() as f64 / space_length.powi(2);
+            // The standard deviation of the count is below 1%.
+            assert!((generated - density).abs() < density * 0.05);
+            assert!(particles.iter().all(|p| {
+                (0.0..space_length).contains(&p.position.x)
+                    && (0.0..space_length).contains(&p.position.y)
+            }));
+        }
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_particles() {
+        let (first, _) = generate(7, 1.0, 20.0, 0.25, 1.0, true);
+        let (second, _) = generate(7, 1.0, 20.0, 0.25, 1.0, true);
+
+        assert_eq!(first.len(), second.len());
+        assert!(first
+            .iter()
+            .zip(&second)
+            .all(|(a, b)| a.id == b.id && a.position == b.position));
+    }
+}
@@ -1,2 +1,3 @@
+pub mod generator;
 pub mod parser;
 pub mod particle;
@@ -6,10 +6,9 @@ use std::{
 
 use chumsky::Parser;
 use clap::Parser as _parser;
-use nalgebra::Vector2;
 use tp2::{
     parser::{input_parser, output_parser},
-    particle::Frame,
+    particle::{order_parameter, Frame},
 };
 
 #[derive(clap::Parser, Debug)]
@@ -44,12 +43,7 @@ fn main() {
         BufReader::new(output_file).lines(),
     ) {
         let Frame { time, particles } = frame;
-        let va = particles
-            .iter()
-            .map(|p| p.velocity_direction)
-            .sum::<Vector2<f64>>()
-            .magnitude()
-            / particles.len() as f64;
+        let va = order_parameter(&particles);
 
         analysis_file
             .write_fmt(format_args!("{time},{va}\n"))
@@ -1,18 +1,24 @@
 use std::{
     collections::BTreeMap,
     fs::{self, File},
-    io::{stdout, Write},
+    io::{self, stdout, BufWriter, Write},
     iter,
+    path::PathBuf,
 };
 
 use chumsky::Parser;
-use cim::{cim_finder::CimNeighborFinder, neighbor_finder::NeighborFinder, particles::ID};
+use cim::{
+    cim_finder::CimNeighborFinder,
+    neighbor_finder::NeighborFinder,
+    particles::{CircularParticle, ID},
+};
 use itertools::Itertools;
 use nalgebra::{Rotation2, Vector2};
 use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+use simulation::{drive, stop_on_interrupt, Simulation};
 use tp2::{
     parser::input_parser,
-    particle::{Frame, InputData, Particle},
+    particle::{order_parameter, Frame, InputData, Particle},
 };
 
 use clap::Parser as _parser;
@@ -28,26 +34,116 @@ struct Args {
 
     #[arg(short, long)]
     max_duration: Option<f64>,
+
+    /// Stop after writing this many frames.
+    #[arg(long)]
+    steps: Option<usize>,
+
+    /// Seed for the noise, takes precedence over the one in the input file.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(long)]
+    unwrapped_output: bool,
+
+    /// Weight of its current heading in the new heading of each particle, between 0 and 1.
+    #[arg(long, default_value_t = 0.0, value_parser = fraction)]
+    inertia: f64,
+
+    #[arg(long, default_value_t = 1)]
+    averaging_passes: usize,
+
+    /// Constant drift added to the displacement of every particle on each step.
+    #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+    gravity: Option<Vec<f64>>,
+
+    /// Where to write the order parameter of each step, as `time,va` lines.
+    #[arg(long)]
+    order_output: Option<PathBuf>,
 }
 
-fn run<W: Write, F: FnMut(&BTreeMap<ID, Particle>, f64) -> bool>(
-    config: InputData,
-    mut output_writer: W,
-    mut stop_condition: F,
-) {
-    let dt = 1.0;
-    let mut time = 0.0;
-    let mut state: BTreeMap<_, _> = config.particles.into_iter().map(|p| (p.id, p)).collect();
-    let mut rng = if let Some(seed) = config.rng_seed {
-        StdRng::seed_from_u64(seed)
+/// Parses a number between 0 and 1.
+fn fraction(value: &str) -> Result<f64, String> {
+    let value: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
     } else {
-        StdRng::from_entropy()
-    };
+        Err(format!("{value} is not between 0 and 1"))
+    }
+}
+
+struct Options {
+    unwrapped_output: bool,
+    inertia: f64,
+    averaging_passes: usize,
+    gravity: Vector2<f64>,
+    order_writer: Option<BufWriter<File>>,
+}
+
+/// Time between steps.
+const DT: f64 = 1.0;
+
+struct Vicsek {
+    config: InputData,
+    options: Options,
+    time: f64,
+    state: BTreeMap<ID, Particle>,
+    /// Positions without the periodic wrapping, only used for the output.
+    unwrapped_positions: BTreeMap<ID, Vector2<f64>>,
+    rng: StdRng,
+}
+
+impl Vicsek {
+    fn new(mut config: InputData, options: Options) -> Self {
+        let state: BTreeMap<_, _> = config.particles.drain(..).map(|p| (p.id, p)).collect();
+        let unwrapped_positions = state.iter().map(|(&id, p)| (id, p.position)).collect();
+        // The seed is always explicit so that any run can be reproduced.
+        let seed = config.rng_seed.unwrap_or_else(|| {
+            let seed = StdRng::from_entropy().gen();
+            eprintln!("Using random seed {seed}");
+            seed
+        });
+
+        Self {
+            config,
+            options,
+            time: 0.0,
+            state,
+            unwrapped_positions,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
 
-    while !stop_condition(&state, time) {
-        let m = (config.space_length / config.interaction_radius).floor() as usize;
+    fn displacement(&self, p: &Particle) -> Vector2<f64> {
+        (p.velocity_direction * self.config.speed + self.options.gravity) * DT
+    }
+}
+
+impl Simulation for Vicsek {
+    type State = BTreeMap<ID, Particle>;
+    type Time = f64;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self) {
+        let config = &self.config;
+        let options = &self.options;
+
+        let particles = self.state.values().cloned().collect_vec();
+        let max_radius = particles.iter().map(|p| p.get_radius()).fold(0.0, f64::max);
+        let m = cim::cim_finder::SystemInfo::cells_along(
+            config.space_length,
+            config.interaction_radius,
+            max_radius,
+        );
         let neighbors = CimNeighborFinder::find_neighbors(
-            &state.values().cloned().collect_vec(),
+            &particles,
             cim::cim_finder::SystemInfo {
                 cyclic: true,
                 interaction_radius: config.interaction_radius,
@@ -55,20 +151,41 @@ fn run<W: Write, F: FnMut(&BTreeMap<ID, Particle>, f64) -> bool>(
                 space_height: config.space_length,
                 columns: m,
                 rows: m,
+                max_neighbors: None,
             },
         );
 
+        // Positions don't change between passes, so the neighbors are found only once.
+        let mut headings: BTreeMap<_, _> = self
+            .state
+            .iter()
+            .map(|(&id, p)| (id, p.velocity_direction))
+            .collect();
+        for _ in 0..options.averaging_passes {
+            headings = headings
+                .iter()
+                .map(|(&id, &heading)| {
+                    let sums = neighbors
+                        .get_neighbors(id)
+                        .chain(iter::once(&id))
+                        .map(|i| headings[i])
+                        .sum::<Vector2<_>>();
+
+                    // With inertia the new heading is a blend of the current one and the average.
+                    let heading = heading * options.inertia
+                        + sums.try_normalize(0.0).unwrap_or_else(Vector2::zeros)
+                            * (1.0 - options.inertia);
+
+                    (id, heading)
+                })
+                .collect();
+        }
+
         let mut new_state = BTreeMap::new();
-        for (&id, particle) in &state {
-            let sums = neighbors
-                .get_neighbors(id)
-                .chain(iter::once(&id))
-                .map(|i| state[i])
-                .map(|n| n.velocity_direction)
-                .sum::<Vector2<_>>();
-
-            let angle = f64::atan2(sums.y, sums.x)
-                + rng.sample(Uniform::new_inclusive(
+        for (&id, particle) in &self.state {
+            let heading = headings[&id];
+            let angle = f64::atan2(heading.y, heading.x)
+                + self.rng.sample(Uniform::new_inclusive(
                     -config.noise / 2.0,
                     config.noise / 2.0,
                 ));
@@ -79,19 +196,46 @@ fn run<W: Write, F: FnMut(&BTreeMap<ID, Particle>, f64) -> bool>(
                 id,
                 Particle {
                     id,
-                    position: (particle.position + particle.velocity_direction * config.speed * dt)
+                    position: (particle.position + self.displacement(particle))
                         .apply_into(|f| *f = f.rem_euclid(config.space_length)),
                     velocity_direction: new_velocity,
                 },
             );
         }
+        for (id, particle) in &self.state {
+            let displacement = self.displacement(particle);
+            *self.unwrapped_positions.get_mut(id).unwrap() += displacement;
+        }
+        self.state = new_state;
+        self.time += DT;
+    }
+
+    fn emit<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
         let frame = Frame {
-            time,
-            particles: state.values().cloned().collect_vec(),
+            time: self.time,
+            particles: self
+                .state
+                .values()
+                .map(|p| Particle {
+                    position: if self.options.unwrapped_output {
+                        self.unwrapped_positions[&p.id]
+                    } else {
+                        p.position
+                    },
+                    ..*p
+                })
+                .collect_vec(),
         };
-        output_writer.write_fmt(format_args!("{frame}")).unwrap();
-        state = new_state;
-        time += dt;
+        writer.write_fmt(format_args!("{frame}"))?;
+        if let Some(order_writer) = &mut self.options.order_writer {
+            writeln!(
+                order_writer,
+                "{},{}",
+                self.time,
+                order_parameter(self.state.values())
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -99,19 +243,195 @@ fn main() {
     let args = Args::parse();
 
     let input = fs::read_to_string(args.input).unwrap();
-    let input = input_parser()
+    let mut input = input_parser()
         .parse(&input)
         .into_result()
         .expect("Error parsing input data.");
+    if let Some(seed) = args.seed {
+        input.rng_seed = Some(seed);
+    }
 
     let writer = if let Some(output) = args.output {
-        Box::new(File::create(output).unwrap()) as Box<dyn Write>
+        Box::new(BufWriter::new(File::create(output).unwrap())) as Box<dyn Write>
     } else {
         Box::new(stdout())
     };
 
-    run(input, writer, |_state, t| {
+    let options = Options {
+        unwrapped_output: args.unwrapped_output,
+        inertia: args.inertia,
+        averaging_passes: args.averaging_passes,
+        gravity: args
+            .gravity
+            .map_or_else(Vector2::zeros, |g| Vector2::new(g[0], g[1])),
+        order_writer: args
+            .order_output
+            .map(|path| BufWriter::new(File::create(path).unwrap())),
+    };
+
+    stop_on_interrupt();
+    // Called after every written frame.
+    let mut written_steps = 0;
+    drive(&mut Vicsek::new(input, options), writer, |_state, t| {
+        written_steps += 1;
         args.max_duration
-            .is_some_and(|max_duration| t > max_duration)
-    });
+            .is_some_and(|max_duration| t >= max_duration)
+            || args.steps.is_some_and(|steps| written_steps >= steps)
+    })
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use tp2::parser::output_parser;
+
+    fn input(particles: Vec<Particle>, noise: f64) -> InputData {
+        InputData {
+            rng_seed: Some(0),
+            space_length: 10.0,
+            interaction_radius: 1.0,
+            noise,
+            speed: 0.3,
+            particles,
+        }
+    }
+
+    fn options() -> Options {
+        Options {
+            unwrapped_output: false,
+            inertia: 0.0,
+            averaging_passes: 1,
+            gravity: Vector2::zeros(),
+            order_writer: None,
+        }
+    }
+
+    /// `count` particles spread over the space with random headings.
+    fn random_particles(count: usize, seed: u64) -> Vec<Particle> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|id| Particle {
+                id,
+                position: Vector2::new(rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0)),
+                velocity_direction: Rotation2::new(rng.gen_range(0.0..std::f64::consts::TAU))
+                    .transform_vector(&Vector2::x()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unwrapped_positions_keep_growing_across_the_border() {
+        let particles = (0..3)
+            .map(|id| Particle {
+                id,
+                position: Vector2::new(9.0, id as f64 * 3.0),
+                velocity_direction: Vector2::x(),
+            })
+            .collect();
+        let mut simulation = Vicsek::new(
+            input(particles, 0.0),
+            Options {
+                unwrapped_output: true,
+                ..options()
+            },
+        );
+
+        let mut output = vec![];
+        simulation.emit(&mut output).unwrap();
+        for _ in 0..50 {
+            simulation.step();
+            simulation.emit(&mut output).unwrap();
+        }
+
+        let frames = output_parser(3, output.as_slice().lines()).collect_vec();
+        assert_eq!(frames.len(), 51);
+        for (before, after) in frames.iter().tuple_windows() {
+            for (a, b) in before.particles.iter().zip(&after.particles) {
+                assert!(b.position.x > a.position.x);
+            }
+        }
+        assert!(frames[50].particles.iter().all(|p| p.position.x > 20.0));
+    }
+
+    #[test]
+    fn full_inertia_keeps_the_headings() {
+        let particles = random_particles(100, 1);
+        let initial = particles.iter().map(|p| p.velocity_direction).collect_vec();
+        let mut simulation = Vicsek::new(
+            input(particles, 0.0),
+            Options {
+                inertia: 1.0,
+                ..options()
+            },
+        );
+
+        for _ in 0..10 {
+            simulation.step();
+        }
+
+        for (particle, heading) in simulation.state.values().zip(initial) {
+            assert!((particle.velocity_direction - heading).magnitude() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn more_averaging_passes_align_more() {
+        let order_after_a_step = |averaging_passes| {
+            let mut simulation = Vicsek::new(
+                input(random_particles(400, 2), 0.0),
+                Options {
+                    averaging_passes,
+                    ..options()
+                },
+            );
+            simulation.step();
+            order_parameter(simulation.state.values())
+        };
+
+        let orders = [1, 3, 10].map(order_after_a_step);
+        assert!(orders.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn empty_and_single_particle_inputs_run() {
+        for count in [0, 1] {
+            let mut simulation = Vicsek::new(input(random_particles(count, 3), 0.5), options());
+            let mut output = vec![];
+            simulation.emit(&mut output).unwrap();
+            for _ in 0..5 {
+                simulation.step();
+                simulation.emit(&mut output).unwrap();
+            }
+
+            assert_eq!(simulation.state.len(), count);
+            let frames = output_parser(count, output.as_slice().lines()).collect_vec();
+            assert_eq!(frames.len(), 6);
+        }
+    }
+
+    #[test]
+    fn gravity_makes_particles_drift_downwards() {
+        let particles = random_particles(100, 4);
+        let start = particles.iter().map(|p| (p.id, p.position)).collect_vec();
+        // With the most noise the headings are random, so only the gravity moves them on average.
+        let mut simulation = Vicsek::new(
+            input(particles, std::f64::consts::TAU),
+            Options {
+                gravity: Vector2::new(0.0, -0.1),
+                ..options()
+            },
+        );
+        for _ in 0..50 {
+            simulation.step();
+        }
+
+        let mean_displacement = start
+            .iter()
+            .map(|(id, position)| simulation.unwrapped_positions[id] - position)
+            .sum::<Vector2<f64>>()
+            / start.len() as f64;
+        assert!((mean_displacement - Vector2::new(0.0, -5.0)).magnitude() < 1.0);
+    }
 }
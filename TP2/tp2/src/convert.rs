@@ -0,0 +1,95 @@
+use cim::{cim_finder::SystemInfo, particles::CircularParticle};
+use nalgebra::Vector2;
+use tp1::particle::{Particle as Tp1Particle, ParticlesData};
+
+use crate::particle::{InputData, Particle};
+
+/// TP1 particles have no velocity, so they get a zero `velocity_direction`. Their radius is
+/// dropped since TP2 particles are points.
+pub fn tp1_to_particles(data: ParticlesData) -> Vec<Particle> {
+    data.particles
+        .into_iter()
+        .map(|p| Particle {
+            id: p.id,
+            position: p.position,
+            velocity_direction: Vector2::zeros(),
+        })
+        .collect()
+}
+
+/// Builds the TP1 neighbor search input for the current positions of `input`, using the same
+/// grid size the simulation uses.
+pub fn input_to_tp1(input: &InputData) -> ParticlesData {
+    let max_radius = input
+        .particles
+        .iter()
+        .map(|p| p.get_radius())
+        .fold(0.0, f64::max);
+    ParticlesData {
+        space_length: input.space_length,
+        grid_size: SystemInfo::cells_along(
+            input.space_length,
+            input.interaction_radius,
+            max_radius,
+        ),
+        interaction_radius: input.interaction_radius,
+        particles: input
+            .particles
+            .iter()
+            .map(|p| Tp1Particle {
+                id: p.id,
+                position: p.position,
+                radius: 0.0,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tp1_data() -> ParticlesData {
+        ParticlesData {
+            space_length: 20.0,
+            grid_size: 20,
+            interaction_radius: 1.0,
+            particles: vec![
+                Tp1Particle {
+                    id: 1,
+                    position: Vector2::new(0.5, 19.5),
+                    radius: 0.0,
+                },
+                Tp1Particle {
+                    id: 7,
+                    position: Vector2::new(10.0, 3.25),
+                    radius: 0.0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn tp1_round_trip_keeps_ids_and_positions() {
+        let tp1 = tp1_data();
+        let input = InputData {
+            rng_seed: None,
+            space_length: tp1.space_length,
+            interaction_radius: tp1.interaction_radius,
+            noise: 0.0,
+            speed: 0.03,
+            particles: tp1_to_particles(tp1_data()),
+        };
+
+        let round_trip = input_to_tp1(&input);
+
+        assert_eq!(round_trip.space_length, tp1.space_length);
+        assert_eq!(round_trip.interaction_radius, tp1.interaction_radius);
+        assert_eq!(round_trip.grid_size, tp1.grid_size);
+        assert_eq!(round_trip.particles.len(), tp1.particles.len());
+        for (after, before) in round_trip.particles.iter().zip(&tp1.particles) {
+            assert_eq!(after.id, before.id);
+            assert_eq!(after.position, before.position);
+        }
+    }
+}
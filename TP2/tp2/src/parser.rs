@@ -29,7 +29,6 @@ pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich
 
     let particles = particle_data
         .separated_by(newline())
-        .at_least(1)
         .allow_trailing()
         .collect();
 
@@ -1,7 +1,7 @@
-use std::fmt::Display;
-
 use cim::particles::{CircularParticle, ID};
+use itertools::Itertools;
 use nalgebra::Vector2;
+use simulation::frame::FrameSerialize;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Particle {
@@ -34,26 +34,150 @@ pub struct InputData {
     pub particles: Vec<Particle>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Frame {
-    pub time: f64,
-    pub particles: Vec<Particle>,
+/// Polarization `va = |sum(v_i)| / (N * speed)`, 1 when every particle moves in the same
+/// direction and close to 0 when they move randomly. Since every particle moves at the same speed
+/// only their directions are needed. Zero when there are no particles.
+pub fn order_parameter<'a>(particles: impl IntoIterator<Item = &'a Particle>) -> f64 {
+    let (sum, count) = particles
+        .into_iter()
+        .fold((Vector2::zeros(), 0), |(sum, count), p| {
+            (sum + p.velocity_direction, count + 1)
+        });
+    if count == 0 {
+        0.0
+    } else {
+        sum.magnitude() / count as f64
+    }
+}
+
+pub type Frame = simulation::frame::Frame<Particle>;
+
+impl FrameSerialize for Particle {
+    fn write_line<W: std::fmt::Write>(&self, f: &mut W) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.id,
+            self.position.x,
+            self.position.y,
+            self.velocity_direction.x,
+            self.velocity_direction.y
+        )
+    }
+}
+
+/// Keeps only the particles of `frame` inside the box between `min` and `max`. When
+/// `space_length` is given the space is treated as periodic, so the box may extend past the
+/// borders and the wrapped images of the particles that fall inside it are included too.
+pub fn crop(
+    frame: &Frame,
+    min: Vector2<f64>,
+    max: Vector2<f64>,
+    space_length: Option<f64>,
+) -> Frame {
+    let offsets = match space_length {
+        Some(l) => vec![-l, 0.0, l],
+        None => vec![0.0],
+    };
+    let is_inside = |p: &Vector2<f64>| p.x >= min.x && p.y >= min.y && p.x <= max.x && p.y <= max.y;
+
+    let particles = frame
+        .particles
+        .iter()
+        .flat_map(|particle| {
+            offsets
+                .iter()
+                .cartesian_product(offsets.iter())
+                .map(|(&dx, &dy)| Particle {
+                    position: particle.position + Vector2::new(dx, dy),
+                    ..*particle
+                })
+                .filter(|p| is_inside(&p.position))
+                .collect_vec()
+        })
+        .collect();
+
+    Frame {
+        time: frame.time,
+        particles,
+    }
 }
 
-impl Display for Frame {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}\n", self.time))?;
-        for particle in &self.particles {
-            f.write_fmt(format_args!(
-                "{} {} {} {} {}\n",
-                particle.id,
-                particle.position.x,
-                particle.position.y,
-                particle.velocity_direction.x,
-                particle.velocity_direction.y
-            ))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn particle(id: ID, x: f64, y: f64) -> Particle {
+        Particle {
+            id,
+            position: Vector2::new(x, y),
+            velocity_direction: Vector2::x(),
+        }
+    }
+
+    fn frame() -> Frame {
+        Frame {
+            time: 1.5,
+            particles: vec![
+                particle(0, 1.0, 1.0),
+                particle(1, 4.0, 4.0),
+                particle(2, 9.5, 5.0),
+            ],
         }
+    }
+
+    #[test]
+    fn crop_keeps_the_particles_inside_the_box() {
+        let cropped = crop(
+            &frame(),
+            Vector2::new(0.0, 0.0),
+            Vector2::new(5.0, 5.0),
+            None,
+        );
+
+        assert_eq!(cropped.time, 1.5);
+        let ids = cropped.particles.iter().map(|p| p.id).collect_vec();
+        assert_eq!(ids, [0, 1]);
+    }
+
+    #[test]
+    fn periodic_crop_includes_wrapped_particles() {
+        let cropped = crop(
+            &frame(),
+            Vector2::new(-1.0, 0.0),
+            Vector2::new(2.0, 6.0),
+            Some(10.0),
+        );
+
+        let positions = cropped
+            .particles
+            .iter()
+            .map(|p| (p.id, p.position))
+            .collect_vec();
+        assert_eq!(
+            positions,
+            [(0, Vector2::new(1.0, 1.0)), (2, Vector2::new(-0.5, 5.0))]
+        );
+    }
+
+    #[test]
+    fn order_parameter_of_aligned_and_random_headings() {
+        let aligned = (0..100).map(|id| particle(id, 0.0, 0.0)).collect_vec();
+        assert!((order_parameter(&aligned) - 1.0).abs() < 1e-12);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let random = (0..10_000)
+            .map(|id| {
+                let angle: f64 = rng.gen_range(0.0..std::f64::consts::TAU);
+                Particle {
+                    velocity_direction: Vector2::new(angle.cos(), angle.sin()),
+                    ..particle(id, 0.0, 0.0)
+                }
+            })
+            .collect_vec();
+        assert!(order_parameter(&random) < 0.05);
 
-        Ok(())
+        assert_eq!(order_parameter(&[]), 0.0);
     }
 }
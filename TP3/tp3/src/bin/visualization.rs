@@ -4,13 +4,12 @@ use capturable_visualization::VisualizationBuilder;
 use chumsky::Parser;
 use clap::Parser as _parser;
 use itertools::Either;
-use nalgebra::Vector2;
 use nannou::prelude::*;
 use pool::{
     draw::draw as draw_pool,
-    models::{Ball, Frame, InputData},
+    models::{Frame, InputData},
     parser::{input_parser, output_parser},
-    Float, HOLE_POSITIONS,
+    Float, Hole,
 };
 use std::{
     fs::{read_to_string, File},
@@ -51,7 +50,7 @@ struct Model {
     frame_iter: Box<dyn Iterator<Item = Frame>>,
     frame: Frame,
     last_frame: Option<Frame>,
-    holes: Vec<Vector2<Float>>,
+    holes: Vec<Hole>,
     time: Float,
 }
 
@@ -65,18 +64,13 @@ fn model(_app: &App, args: Args) -> Model {
 
     let frame_iter = Box::new(output_parser(BufReader::new(output_file).lines()));
 
-    let holes = Vec::from(HOLE_POSITIONS.map(|v| {
-        v.component_mul(&Vector2::new(
-            system_info.table_width,
-            system_info.table_height,
-        ))
-    }));
+    let holes = system_info.holes();
 
     Model {
         last_frame: None,
         frame: Frame {
             time: 0.0,
-            balls: system_info.balls.clone(),
+            particles: system_info.balls.clone(),
         },
         time: 0.0,
         frame_iter,
@@ -93,28 +87,21 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
         model.last_frame = Some(model.frame.clone());
         model.frame = model.frame_iter.next().unwrap_or_else(|| Frame {
             time: Float::INFINITY,
-            balls: model.last_frame.as_ref().unwrap().balls.clone(),
+            particles: model.last_frame.as_ref().unwrap().particles.clone(),
         });
     }
 }
 
 fn draw(_app: &App, model: &Model, draw: &Draw) {
     let interpolated_balls = if let Some(last_frame) = &model.last_frame {
-        Either::Left(last_frame.balls.iter().map(
-            |&Ball {
-                 id,
-                 position,
-                 velocity,
-                 radius,
-             }| Ball {
-                id,
-                position: position + velocity * (model.time - last_frame.time),
-                velocity,
-                radius,
-            },
-        ))
+        Either::Left(
+            last_frame
+                .particles
+                .iter()
+                .map(|ball| ball.advance(model.time - last_frame.time)),
+        )
     } else {
-        Either::Right(model.frame.balls.iter().cloned())
+        Either::Right(model.frame.particles.iter().cloned())
     };
 
     draw_pool(&model.system_info, interpolated_balls, &model.holes, draw);
@@ -1,7 +1,14 @@
+import argparse
 import random
-from typing import Optional
+from typing import Optional, Sequence, Union
 import math
 
+# Amount of balls in each column of the rack, starting from the one closest to the white ball.
+RACK_PRESETS = {
+    "triangle15": [1, 2, 3, 4, 5],
+    "diamond9": [1, 2, 3, 2, 1],
+}
+
 
 def rand_inside_circle(radius: float):
     r = radius * math.sqrt(random.random())
@@ -13,6 +20,41 @@ def rand_inside_circle(radius: float):
     return x, y
 
 
+def rack_positions(
+    rack: Union[str, Sequence[tuple[float, float]]],
+    apex_x: float,
+    center_y: float,
+    separation: float,
+) -> list[tuple[float, float]]:
+    """Positions of the racked balls. `rack` is either the name of a preset or a list of
+    custom positions relative to the apex of the rack."""
+    if not isinstance(rack, str):
+        return [(apex_x + x, center_y + y) for x, y in rack]
+
+    column_height = math.sqrt(separation**2 - (separation / 2) ** 2)
+    positions = []
+    for column, count in enumerate(RACK_PRESETS[rack]):
+        x = apex_x + column * column_height
+        for i in range(count):
+            positions.append((x, center_y + (i - (count - 1) / 2) * separation))
+    return positions
+
+
+def validate_positions(
+    positions: Sequence[tuple[float, float]],
+    table_width: float,
+    table_height: float,
+    ball_diameter: float,
+):
+    radius = ball_diameter / 2
+    for i, (x, y) in enumerate(positions):
+        if not (radius <= x <= table_width - radius and radius <= y <= table_height - radius):
+            raise ValueError(f"Ball at ({x}, {y}) is outside the table")
+        for other_x, other_y in positions[i + 1 :]:
+            if math.dist((x, y), (other_x, other_y)) < ball_diameter:
+                raise ValueError(f"Balls at ({x}, {y}) and ({other_x}, {other_y}) overlap")
+
+
 def generate(
     table_width: float,
     table_height: float,
@@ -22,12 +64,23 @@ def generate(
     ball_mass: float,
     seed: Optional[int] = None,
     speed: int = 200,
+    rack: Union[str, Sequence[tuple[float, float]]] = "triangle15",
 ) -> str:
     if seed is not None:
         random.seed(seed)
     out: str = ""
 
-    out += f"{table_width}\n{table_height}\n{hole_diameter}\n{ball_diameter}\n{ball_mass}\n{16}\n"
+    min_separation = 0.02
+    max_separation = 0.03
+
+    initial_separation = ball_diameter + max_separation / 2 + min_separation / 2
+    max_random_module = (max_separation - min_separation) / 4
+
+    white = (table_width / 4, white_y)
+    racked = rack_positions(rack, table_width * 3 / 4, table_height / 2, initial_separation)
+    validate_positions([white, *racked], table_width, table_height, ball_diameter)
+
+    out += f"{table_width}\n{table_height}\n{hole_diameter}\n{ball_diameter}\n{ball_mass}\n{len(racked) + 1}\n"
 
     ball_id: int = 0
 
@@ -36,27 +89,20 @@ def generate(
         out += f"{ball_id} {x} {y} {vx} {vy}\n"
         ball_id += 1
 
-    add_ball(table_width / 4, white_y, speed, 0)
-
-    min_separation = 0.02
-    max_separation = 0.03
-
-    initial_separation = ball_diameter + max_separation / 2 + min_separation / 2
-    max_random_module = (max_separation - min_separation) / 4
+    add_ball(*white, speed, 0)
 
-    triangle_height = math.sqrt(initial_separation**2 - (initial_separation / 2) ** 2)
-
-    for rank in range(5):
-        x = table_width * 3 / 4 + rank * triangle_height
-        for i in range(rank + 1):
-            y = table_height / 2 + ((i - rank / 2) * initial_separation)
-            rx, ry = rand_inside_circle(max_random_module)
-            add_ball(x + rx, y + ry)
+    for x, y in racked:
+        rx, ry = rand_inside_circle(max_random_module)
+        add_ball(x + rx, y + ry)
 
     return out
 
 
 if __name__ == "__main__":
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--rack", choices=RACK_PRESETS.keys(), default="triangle15")
+    args = parser.parse_args()
+
     print(
         generate(
             table_width=224,
@@ -66,6 +112,7 @@ if __name__ == "__main__":
             hole_diameter=5.7 * 2,
             ball_diameter=5.7,
             ball_mass=165,
+            rack=args.rack,
         ),
         end="",
     )
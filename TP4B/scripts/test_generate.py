@@ -0,0 +1,35 @@
+import math
+import unittest
+
+from generate import RACK_PRESETS, generate
+
+
+def parse_balls(output: str) -> list[tuple[float, float]]:
+    lines = output.splitlines()
+    count = int(lines[5])
+    balls = [tuple(map(float, line.split()[1:3])) for line in lines[6:]]
+    assert len(balls) == count
+    return balls
+
+
+class RackPresetsTest(unittest.TestCase):
+    def test_presets_have_their_ball_count_without_overlaps(self):
+        ball_diameter = 5.7
+        for preset, columns in RACK_PRESETS.items():
+            with self.subTest(preset=preset):
+                output = generate(224, 112, 50, 11.4, ball_diameter, 165, seed=0, rack=preset)
+                balls = parse_balls(output)
+
+                # The racked balls plus the white one.
+                self.assertEqual(len(balls), sum(columns) + 1)
+                for i, ball in enumerate(balls):
+                    for other in balls[i + 1 :]:
+                        self.assertGreaterEqual(math.dist(ball, other), ball_diameter)
+
+    def test_overlapping_custom_racks_are_rejected(self):
+        with self.assertRaises(ValueError):
+            generate(224, 112, 50, 11.4, 5.7, 165, seed=0, rack=[(0, 0), (1, 0)])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -37,7 +37,10 @@ fn main() {
     let mut analysis_file = File::create(args.analysis).unwrap();
 
     for frame in output_parser(BufReader::new(output_file).lines()) {
-        let Frame { time, balls } = frame;
+        let Frame {
+            time,
+            particles: balls,
+        } = frame;
         let energy: Float = balls
             .iter()
             .map(|p| 0.5 * system_info.ball_mass * p.velocity.magnitude().powi(2))
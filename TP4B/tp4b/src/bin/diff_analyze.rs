@@ -1,11 +1,16 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{BufRead, BufReader, Write},
     path::PathBuf,
 };
 
+use cim::particles::ID;
 use clap::Parser as _parser;
-use pool::{models::Frame, parser::output_parser};
+use pool::{
+    models::{Ball, Frame},
+    parser::output_parser,
+};
 
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -34,11 +39,11 @@ fn main() {
     {
         let Frame {
             time: time1,
-            balls: mut balls1,
+            particles: balls1,
         } = frame1;
         let Frame {
             time: time2,
-            balls: mut balls2,
+            particles: balls2,
         } = frame2;
 
         //assert_eq!(time1, time2);
@@ -47,16 +52,46 @@ fn main() {
         assert!(time_diff < 1e-6, "time1: {} | time2: {}", time1, time2);
         let time = time1;
 
-        balls1.sort_by_key(|ball| ball.id);
-        balls2.sort_by_key(|ball| ball.id);
-        let phi: f64 = balls1
-            .iter()
-            .zip(balls2.iter())
-            .map(|(ball1, ball2)| (ball2.position - ball1.position).magnitude())
-            .sum();
+        let phi = phi(&balls1, &balls2);
 
         analysis_file
             .write_fmt(format_args!("{time},{phi}\n"))
             .unwrap();
     }
 }
+
+/// Sum of the distances between the positions of each ball in both runs.
+fn phi(balls1: &[Ball], balls2: &[Ball]) -> f64 {
+    // Balls may have been pocketed in one of the runs but not in the other, so they are matched by
+    // id and only the ones present in both frames are compared.
+    let positions2: BTreeMap<ID, _> = balls2.iter().map(|b| (b.id, b.position)).collect();
+    balls1
+        .iter()
+        .filter_map(|ball1| {
+            positions2
+                .get(&ball1.id)
+                .map(|position2| (position2 - ball1.position).magnitude())
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(output: &str) -> Vec<Ball> {
+        output_parser(output.as_bytes().lines())
+            .next()
+            .unwrap()
+            .particles
+    }
+
+    #[test]
+    fn balls_pocketed_in_one_run_are_skipped() {
+        let balls1 = frame("3\n0\n0 0 0 0 0\n1 10 10 0 0\n2 20 20 0 0\n");
+        let balls2 = frame("2\n0\n0 3 4 0 0\n2 20 21 0 0\n");
+
+        assert_eq!(phi(&balls1, &balls2), 6.0);
+        assert_eq!(phi(&balls2, &balls1), 6.0);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fs::{read_to_string, File},
+    io::{self, stdout, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use chumsky::Parser;
+use clap::Parser as _parser;
+use nalgebra::Vector2;
+use pool::{
+    models::{Ball, Frame, InputData},
+    parser::{input_parser, output_parser},
+    Float,
+};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Metric {
+    /// Total kinetic energy.
+    Energy,
+    /// Amount of balls still on the table.
+    BallCount,
+    /// Norm of the mean velocity over the mean speed, 1 when every ball moves in the same direction.
+    OrderParameter,
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::Energy => "energy",
+            Metric::BallCount => "ball_count",
+            Metric::OrderParameter => "order_parameter",
+        }
+    }
+
+    fn calculate(&self, balls: &[Ball], config: &InputData) -> Float {
+        match self {
+            Metric::Energy => balls
+                .iter()
+                .map(|b| 0.5 * config.ball_mass * b.velocity.magnitude_squared())
+                .sum(),
+            Metric::BallCount => balls.len() as Float,
+            Metric::OrderParameter => {
+                let total_speed: Float = balls.iter().map(|b| b.velocity.magnitude()).sum();
+                if total_speed == 0.0 {
+                    0.0
+                } else {
+                    balls
+                        .iter()
+                        .map(|b| b.velocity)
+                        .sum::<Vector2<Float>>()
+                        .magnitude()
+                        / total_speed
+                }
+            }
+        }
+    }
+}
+
+/// Writes a whitespace delimited table with a `time` column followed by one column per metric,
+/// which can be plotted directly with gnuplot or loaded with numpy.
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    input: PathBuf,
+
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Where to write the table, stdout if not given.
+    #[arg(short, long)]
+    analysis: Option<PathBuf>,
+
+    #[arg(short, long, value_delimiter = ',', default_value = "energy")]
+    metrics: Vec<Metric>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let input = read_to_string(args.input).unwrap();
+    let output_file = File::open(args.output).unwrap();
+    let config = input_parser()
+        .parse(&input)
+        .into_result()
+        .expect("Error parsing input data.");
+
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(match args.analysis {
+        Some(path) => Box::new(File::create(path).unwrap()),
+        None => Box::new(stdout()),
+    });
+
+    export(
+        &mut writer,
+        output_parser(BufReader::new(output_file).lines()),
+        &args.metrics,
+        &config,
+    )
+    .unwrap();
+    writer.flush().unwrap();
+}
+
+fn export<W: Write>(
+    writer: &mut W,
+    frames: impl Iterator<Item = Frame>,
+    metrics: &[Metric],
+    config: &InputData,
+) -> io::Result<()> {
+    // gnuplot skips lines starting with '#'.
+    write!(writer, "# time")?;
+    for metric in metrics {
+        write!(writer, " {}", metric.name())?;
+    }
+    writeln!(writer)?;
+
+    for frame in frames {
+        write!(writer, "{}", frame.time)?;
+        for metric in metrics {
+            write!(writer, " {}", metric.calculate(&frame.particles, config))?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "224
+112
+11.4
+5.7
+165
+2
+0 10 10 3 4
+1 50 50 0 0
+";
+
+    const OUTPUT: &str = "2
+0
+0 10 10 3 4
+1 50 50 0 0
+2
+0.5
+0 11.5 12 3 4
+1 50 50 0 0
+";
+
+    #[test]
+    fn one_column_per_metric() {
+        let config = input_parser().parse(INPUT).into_result().unwrap();
+        let frames = output_parser(OUTPUT.as_bytes().lines());
+        let metrics = [Metric::Energy, Metric::BallCount, Metric::OrderParameter];
+
+        let mut table = vec![];
+        export(&mut table, frames, &metrics, &config).unwrap();
+
+        let table = String::from_utf8(table).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(
+            lines.next(),
+            Some("# time energy ball_count order_parameter")
+        );
+        let rows = lines.collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            assert_eq!(row.split_whitespace().count(), metrics.len() + 1);
+        }
+    }
+}
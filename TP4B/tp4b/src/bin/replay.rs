@@ -0,0 +1,204 @@
+use capturable_visualization::VisualizationBuilder;
+use chumsky::Parser;
+use clap::Parser as _parser;
+use nannou::prelude::*;
+use pool::{
+    binary::binary_output_parser,
+    draw::draw as draw_pool,
+    json::json_output_parser,
+    models::{Frame, InputData, OutputFormat},
+    parser::{input_parser, output_parser},
+    table_holes, Float, Hole,
+};
+use std::{
+    fs::{read_to_string, File},
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+/// Plays back a simulation output at its own pace.
+///
+/// Space pauses, the left and right arrows step one frame back or forward (jumping
+/// `--scrub-seconds` when holding shift) and the up and down arrows double or halve the speed.
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    input: PathBuf,
+
+    #[arg(short, long)]
+    output: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Factor the simulation multiplied the output times by with `--time-scale`.
+    #[arg(long, default_value_t = 1.0)]
+    time_scale: Float,
+
+    /// Simulated seconds played per real second.
+    #[arg(long, default_value_t = 1.0)]
+    speed: Float,
+
+    /// Simulated seconds to jump when scrubbing with shift and the arrows.
+    #[arg(long, default_value_t = 1.0)]
+    scrub_seconds: Float,
+
+    #[arg(long)]
+    capture_directory: Option<PathBuf>,
+
+    /// Capture as if playing at this many frames per second, regardless of how long each frame
+    /// takes to draw.
+    #[arg(long, requires = "capture_directory")]
+    capture_fps: Option<f64>,
+
+    /// Exit after capturing this many frames.
+    #[arg(long, requires = "capture_directory")]
+    capture_frames: Option<usize>,
+
+    #[arg(short, long)]
+    no_holes: bool,
+
+    #[arg(long)]
+    side_hole_radius: Option<Float>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let input = read_to_string(&args.input).unwrap();
+    let system_info = input_parser()
+        .parse(&input)
+        .into_result()
+        .expect("Error parsing input data.");
+
+    let capture_directory = args.capture_directory.clone();
+    let capture_fps = args.capture_fps;
+    let capture_frames = args.capture_frames;
+    let aspect_ratio = (system_info.table_width / system_info.table_height) as f32;
+    let mut visualization = VisualizationBuilder::new(|app| model(app, args, system_info))
+        .update(update)
+        .event(event)
+        .draw(draw)
+        .with_aspect_ratio(aspect_ratio);
+
+    if let Some(capture_directory) = capture_directory {
+        visualization = match capture_fps {
+            Some(fps) => visualization.capture(capture_directory, fps),
+            None => visualization.with_capture(
+                capture_directory,
+                ((1080.0 * aspect_ratio).round() as u32, 1080),
+            ),
+        };
+    }
+    if let Some(frames) = capture_frames {
+        visualization = visualization.with_capture_limit(frames);
+    }
+
+    visualization.run();
+}
+
+struct Model {
+    system_info: InputData,
+    frames: Vec<Frame>,
+    holes: Vec<Hole>,
+    /// Index of the frame being shown.
+    current: usize,
+    /// Simulated time being shown, at or after the time of the current frame.
+    time: Float,
+    speed: Float,
+    scrub_seconds: Float,
+    paused: bool,
+}
+
+impl Model {
+    /// Shows the last frame at or before `time`, or the first one if all of them are after it.
+    fn seek(&mut self, time: Float) {
+        let last = self.frames.len() - 1;
+        self.time = time.clamp(self.frames[0].time, self.frames[last].time);
+        self.current = self
+            .frames
+            .partition_point(|frame| frame.time <= self.time)
+            .saturating_sub(1);
+    }
+
+    fn step(&mut self, forward: bool) {
+        self.current = if forward {
+            (self.current + 1).min(self.frames.len() - 1)
+        } else {
+            self.current.saturating_sub(1)
+        };
+        self.time = self.frames[self.current].time;
+    }
+}
+
+fn model(_app: &App, args: Args, system_info: InputData) -> Model {
+    let output_file = File::open(args.output).unwrap();
+    let mut frames: Vec<Frame> = match args.output_format {
+        OutputFormat::Text => output_parser(BufReader::new(output_file).lines()).collect(),
+        OutputFormat::Json => json_output_parser(BufReader::new(output_file).lines()).collect(),
+        OutputFormat::Binary => binary_output_parser(BufReader::new(output_file)).collect(),
+    };
+    for frame in &mut frames {
+        frame.time /= args.time_scale;
+    }
+    assert!(!frames.is_empty(), "The output has no frames.");
+
+    let holes = if args.no_holes {
+        Vec::with_capacity(0)
+    } else {
+        table_holes(
+            system_info.table_width,
+            system_info.table_height,
+            &system_info.hole_positions,
+            system_info.hole_radius,
+            args.side_hole_radius.unwrap_or(system_info.hole_radius),
+        )
+    };
+
+    Model {
+        time: frames[0].time,
+        current: 0,
+        frames,
+        holes,
+        system_info,
+        speed: args.speed,
+        scrub_seconds: args.scrub_seconds,
+        paused: false,
+    }
+}
+
+fn event(app: &App, model: &mut Model, event: WindowEvent) {
+    let WindowEvent::KeyPressed(key) = event else {
+        return;
+    };
+    let scrubbing = app.keys.mods.shift();
+    match key {
+        Key::Space => model.paused = !model.paused,
+        Key::Right | Key::Left if scrubbing => {
+            let direction = if key == Key::Right { 1.0 } else { -1.0 };
+            model.seek(model.time + direction * model.scrub_seconds);
+        }
+        Key::Right | Key::Left => {
+            model.paused = true;
+            model.step(key == Key::Right);
+        }
+        Key::Up => model.speed *= 2.0,
+        Key::Down => model.speed /= 2.0,
+        _ => {}
+    }
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    if !model.paused {
+        model.seek(model.time + update.since_last.as_secs_f64() as Float * model.speed);
+    }
+}
+
+fn draw(_app: &App, model: &Model, draw: &Draw) {
+    draw_pool(
+        &model.system_info,
+        model.frames[model.current].particles.iter().cloned(),
+        &model.holes,
+        draw,
+    );
+}
@@ -3,30 +3,53 @@
 
 use chumsky::Parser;
 use cim::{
-    /*cim_finder::CimNeighborFinder, */ neighbor_finder::NeighborFinder, particles::ID,
+    cim_finder::{approach_margin, CimNeighborFinder},
+    neighbor_finder::NeighborFinder,
+    particles::ID,
     simple_finder::SimpleNeighborFinder,
 };
-use gear_predictor_corrector::{GearCorrector, GearPredictor};
+use gear_predictor_corrector::{
+    beeman::BeemanState,
+    velocity_verlet::{velocity_verlet_position, velocity_verlet_velocity},
+    GearCorrector, GearPredictor,
+};
+use itertools::Itertools;
 use std::{
     collections::{BTreeMap, HashMap},
     fs::{self, File},
-    io::{stdout, Write},
+    io::{self, stdout, BufReader, BufWriter, IsTerminal, Read, Write},
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
 };
 
 use nalgebra::Vector2;
 use pool::{
-    models::{Ball, InputData as SimpleInputData, IterableFrame},
-    parser::input_parser,
-    Float, HOLE_POSITIONS,
+    events::{apply_collision, find_earliest_collision, CollisionAgainst},
+    geometry::clamp_into_table,
+    models::{Ball, InputData as SimpleInputData, IterableFrame, OutputFormat, WithSpin},
+    parser::{input_parser, polar_input_parser},
+    table_holes, Float, Hole,
 };
 
 use clap::{Args, Parser as _parser, Subcommand};
+use simulation::{drive, stop_on_interrupt, Simulation};
 
 #[derive(Subcommand, Debug)]
 #[clap(rename_all = "kebab_case")]
 enum OutputCondition {
     Every(EveryArgs),
+    EverySeconds(EverySecondsArgs),
     WhenBallCountHits { counts: Vec<usize> },
+    PocketEvents,
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum Integrator {
+    #[default]
+    Gear,
+    VelocityVerlet,
+    Beeman,
 }
 
 #[derive(Debug, Args)]
@@ -36,6 +59,14 @@ struct EveryArgs {
     last: bool,
 }
 
+/// Write a frame on the first step at or after every multiple of `seconds` of simulated time.
+#[derive(Debug, Args)]
+struct EverySecondsArgs {
+    seconds: Float,
+    #[arg(long)]
+    last: bool,
+}
+
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Arguments {
@@ -54,11 +85,99 @@ struct Arguments {
     #[arg(short, long)]
     with_holes: bool,
 
-    #[arg(short, long)]
+    #[arg(short, long, alias = "max-time")]
     max_duration: Option<f64>,
 
+    /// Stop once every ball is slower than this speed.
+    #[arg(long)]
+    stop_when_still: Option<Float>,
+
+    /// Warn about balls that went through each other or through a cushion within a single step,
+    /// which means the delta time is too big for their speed.
+    #[arg(long)]
+    check_tunneling: bool,
+
     #[arg(long)]
     min_ball_amount: Option<usize>,
+
+    #[arg(long)]
+    side_hole_radius: Option<Float>,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Split steps in which a ball would move more than this fraction of its radius.
+    #[arg(long)]
+    max_displacement: Option<Float>,
+
+    /// Buffer the output with a buffer of this many bytes.
+    #[arg(long)]
+    buffer_size: Option<usize>,
+
+    /// Overwrite the checkpoint file with the full state every this many steps.
+    #[arg(long)]
+    checkpoint_every: Option<u64>,
+
+    #[arg(long, default_value = "checkpoint.bin")]
+    checkpoint_file: PathBuf,
+
+    /// Continue from a checkpoint written by a run with the same input and delta time.
+    #[arg(long)]
+    restore: Option<PathBuf>,
+
+    /// Read the ball velocities in the input as `speed angle` instead of `vx vy`.
+    #[arg(long)]
+    polar_input: bool,
+
+    /// Report the percentage of `--max-duration` simulated so far and an estimate of the time
+    /// left. Enabled by default when stderr is a terminal and `--max-duration` is given.
+    #[arg(long, requires = "max_duration")]
+    progress: bool,
+
+    /// Write `time,kinetic_energy,ball_count` to this file every time a frame is written.
+    #[arg(long)]
+    energy_output: Option<PathBuf>,
+
+    /// Write `time,steps,wall_time,ball_count,max_speed` to this file every time a frame is
+    /// written.
+    #[arg(long)]
+    metrics_output: Option<PathBuf>,
+
+    /// Write `time,id,fx,fy` with the net force on every ball during the last step, every time
+    /// a frame is written. Hard spheres have no forces, so it can't be used with
+    /// `--event-driven`.
+    #[arg(long, conflicts_with = "event_driven")]
+    force_output: Option<PathBuf>,
+
+    /// Print the parameters the simulation runs with to stderr before starting.
+    #[arg(long)]
+    print_config: bool,
+
+    /// Multiply the times written to the output, the energy and the force logs by this factor, e.g.
+    /// 1000 to write milliseconds.
+    #[arg(long, default_value_t = 1.0)]
+    time_scale: Float,
+
+    /// After every step, separate overlapping balls up to this many times.
+    #[arg(long, default_value_t = 0)]
+    contact_iterations: u32,
+
+    /// Stop separating balls once no overlap is bigger than this distance.
+    #[arg(long, default_value_t = 0.0)]
+    contact_tolerance: Float,
+
+    #[arg(long, value_enum, default_value_t = Integrator::Gear)]
+    integrator: Integrator,
+
+    /// Treat balls as hard spheres and jump from one collision to the next instead of integrating
+    /// with a fixed delta time. Each collision counts as a step for the output condition.
+    #[arg(long)]
+    event_driven: bool,
+
+    /// Apply friction at the contacts so colliding balls spin, and write the angular velocity of
+    /// every ball as an extra column in the text output.
+    #[arg(long, conflicts_with = "event_driven")]
+    with_spin: bool,
 }
 
 struct InputData {
@@ -66,9 +185,27 @@ struct InputData {
     output_condition: OutputCondition,
     delta_time_n: u16,
     with_holes: bool,
+    side_hole_radius: Option<Float>,
+    output_format: OutputFormat,
+    max_displacement: Option<Float>,
+    checkpoint_every: Option<u64>,
+    checkpoint_file: PathBuf,
+    restore: Option<Checkpoint>,
+    time_scale: Float,
+    contact_iterations: u32,
+    contact_tolerance: Float,
+    integrator: Integrator,
+    event_driven: bool,
+    with_spin: bool,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl InputData {
+    fn delta_time(&self) -> Float {
+        (10.0 as Float).powi(-(self.delta_time_n as i32))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Wall {
     Left,
     Right,
@@ -77,6 +214,320 @@ enum Wall {
 }
 
 const K: Float = 10e4 * 1000.0;
+/// Ratio between the biggest tangential force at a contact and the force pushing the balls apart.
+const CONTACT_FRICTION: Float = 0.05;
+/// Tangential force per unit of sliding speed at a contact, up to the limit set by the friction.
+const TANGENTIAL_DAMPING: Float = 1e3;
+const MAX_SUBSTEPS: u32 = 1 << 10;
+/// From this many balls on, contacts are found with the cell index method instead of checking
+/// every pair.
+const CIM_MIN_BALLS: usize = 64;
+
+type State = BTreeMap<ID, (Ball, [Vector2<Float>; 4])>;
+
+/// Hooks called by `PoolSimulation`, used for everything that is not part of the simulation output
+/// itself.
+trait SimulationObserver {
+    fn on_step(&mut self, _state: &State, _time: Float) {}
+    fn on_frame(&mut self, _state: &State, _time: Float) {}
+    /// Net force on every ball during the last step, including balls pocketed in it, or where
+    /// they start before the first step. Called before `on_frame` with the same time.
+    fn on_forces(&mut self, _forces: &HashMap<ID, Vector2<Float>>, _time: Float) {}
+}
+
+struct ProgressObserver {
+    max_duration: Float,
+    last_percent: u32,
+    /// Wall clock and simulated time of the first step, which isn't zero when restoring from a
+    /// checkpoint.
+    start: Option<(Instant, Float)>,
+}
+
+impl SimulationObserver for ProgressObserver {
+    fn on_step(&mut self, _state: &State, time: Float) {
+        let (start_instant, start_time) = *self.start.get_or_insert((Instant::now(), time));
+        let percent = (time / self.max_duration * 100.0).floor() as u32;
+        if percent > self.last_percent {
+            self.last_percent = percent;
+            // Assume the rest of the simulation runs at the same pace as what was done so far.
+            let done = (time - start_time) / (self.max_duration - start_time);
+            if done > 0.0 {
+                let elapsed = start_instant.elapsed().as_secs_f64();
+                let eta = elapsed * (1.0 - done) / done;
+                eprintln!("{percent}% (ETA {eta:.0}s)");
+            } else {
+                eprintln!("{percent}%");
+            }
+        }
+    }
+}
+
+struct EnergyLogger<W: Write> {
+    writer: W,
+    ball_mass: Float,
+    time_scale: Float,
+}
+
+impl<W: Write> SimulationObserver for EnergyLogger<W> {
+    fn on_frame(&mut self, state: &State, time: Float) {
+        let energy: Float = state
+            .values()
+            .map(|(b, _)| 0.5 * self.ball_mass * b.velocity.magnitude_squared())
+            .sum();
+        let time = time * self.time_scale;
+        self.writer
+            .write_fmt(format_args!("{time},{energy},{}\n", state.len()))
+            .unwrap();
+        // Keep the file readable while the simulation is still running.
+        self.writer.flush().unwrap();
+    }
+}
+
+/// Logs how far the simulation got and how long it took: the steps made and the wall clock seconds
+/// since it started, along with the amount of balls and the speed of the fastest one.
+struct MetricsLogger<W: Write> {
+    writer: W,
+    time_scale: Float,
+    steps: u64,
+    start: Instant,
+}
+
+impl<W: Write> SimulationObserver for MetricsLogger<W> {
+    fn on_step(&mut self, _state: &State, _time: Float) {
+        self.steps += 1;
+    }
+
+    fn on_frame(&mut self, state: &State, time: Float) {
+        let time = time * self.time_scale;
+        let wall_time = self.start.elapsed().as_secs_f64();
+        let max_speed = state
+            .values()
+            .map(|(b, _)| b.velocity.magnitude())
+            .fold(0.0, Float::max);
+        self.writer
+            .write_fmt(format_args!(
+                "{time},{},{wall_time},{},{max_speed}\n",
+                self.steps,
+                state.len()
+            ))
+            .unwrap();
+        // Keep the file readable while the simulation is still running.
+        self.writer.flush().unwrap();
+    }
+}
+
+struct ForceLogger<W: Write> {
+    writer: W,
+    last_forces: HashMap<ID, Vector2<Float>>,
+    time_scale: Float,
+}
+
+impl<W: Write> SimulationObserver for ForceLogger<W> {
+    fn on_forces(&mut self, forces: &HashMap<ID, Vector2<Float>>, _time: Float) {
+        self.last_forces.clone_from(forces);
+    }
+
+    fn on_frame(&mut self, state: &State, time: Float) {
+        let time = time * self.time_scale;
+        for id in state.keys() {
+            let force = self
+                .last_forces
+                .get(id)
+                .copied()
+                .unwrap_or_else(Vector2::zeros);
+            self.writer
+                .write_fmt(format_args!("{time},{id},{},{}\n", force.x, force.y))
+                .unwrap();
+        }
+    }
+}
+
+struct TunnelingChecker {
+    ball_radius: Float,
+    table_width: Float,
+    table_height: Float,
+    last_positions: BTreeMap<ID, Vector2<Float>>,
+}
+
+impl TunnelingChecker {
+    /// Whether two balls moving in a straight line during the step got closer than touching at some
+    /// point while neither starting nor ending in contact, so no force ever separated them.
+    fn passed_through(&self, from: Vector2<Float>, to: Vector2<Float>) -> bool {
+        let radius_sum = 2.0 * self.ball_radius;
+        if from.magnitude() < radius_sum || to.magnitude() < radius_sum {
+            return false;
+        }
+        let displacement = to - from;
+        let length_squared = displacement.magnitude_squared();
+        if length_squared == 0.0 {
+            return false;
+        }
+        let t = (-from.dot(&displacement) / length_squared).clamp(0.0, 1.0);
+        (from + displacement * t).magnitude() < radius_sum
+    }
+}
+
+impl SimulationObserver for TunnelingChecker {
+    fn on_step(&mut self, state: &State, time: Float) {
+        let positions: BTreeMap<_, _> = state
+            .iter()
+            .map(|(&id, (ball, _))| (id, ball.position))
+            .collect();
+
+        let moved = positions
+            .iter()
+            .filter_map(|(id, &to)| Some((*id, self.last_positions.get(id).copied()?, to)))
+            .collect_vec();
+        for ((id, from, to), (other_id, other_from, other_to)) in moved.iter().tuple_combinations()
+        {
+            if self.passed_through(from - other_from, to - other_to) {
+                eprintln!("Warning: t = {time}: balls {id} and {other_id} went through each other");
+            }
+        }
+
+        for (id, position) in &positions {
+            let wall = if position.x < 0.0 {
+                Some(Wall::Left)
+            } else if position.x > self.table_width {
+                Some(Wall::Right)
+            } else if position.y < 0.0 {
+                Some(Wall::Bottom)
+            } else if position.y > self.table_height {
+                Some(Wall::Top)
+            } else {
+                None
+            };
+            if let Some(wall) = wall {
+                eprintln!("Warning: t = {time}: ball {id} went through the {wall:?} cushion");
+            }
+        }
+
+        self.last_positions = positions;
+    }
+}
+
+#[derive(Debug)]
+struct Checkpoint {
+    iteration: u64,
+    time: Float,
+    state: State,
+}
+
+fn write_float<W: Write>(writer: &mut W, value: Float) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_float<R: Read>(reader: &mut R) -> io::Result<Float> {
+    let mut bytes = [0; mem::size_of::<Float>()];
+    reader.read_exact(&mut bytes)?;
+    Ok(Float::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"POOLCKPT";
+
+/// Bumped whenever the layout changes. Version 2 added the angular velocity of every ball.
+const CHECKPOINT_VERSION: u32 = 2;
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Little endian layout: magic, format version, iteration, time and ball count, then for every
+/// ball its id, position, velocity, the four higher order derivatives, radius and angular
+/// velocity.
+fn write_checkpoint<W: Write>(
+    writer: &mut W,
+    iteration: u64,
+    time: Float,
+    state: &State,
+) -> io::Result<()> {
+    writer.write_all(CHECKPOINT_MAGIC)?;
+    writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+    writer.write_all(&iteration.to_le_bytes())?;
+    write_float(writer, time)?;
+    writer.write_all(&(state.len() as u64).to_le_bytes())?;
+    for (ball, higher_order) in state.values() {
+        writer.write_all(&(ball.id as u64).to_le_bytes())?;
+        let vectors = [ball.position, ball.velocity]
+            .into_iter()
+            .chain(*higher_order);
+        for value in vectors.flat_map(|v| [v.x, v.y]) {
+            write_float(writer, value)?;
+        }
+        write_float(writer, ball.radius)?;
+        write_float(writer, ball.angular_velocity)?;
+    }
+    Ok(())
+}
+
+/// Writes to a temporary file first so a crash while writing keeps the previous checkpoint.
+fn save_checkpoint(path: &Path, iteration: u64, time: Float, state: &State) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    write_checkpoint(&mut writer, iteration, time, state)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(temporary, path)
+}
+
+impl Checkpoint {
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0; CHECKPOINT_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a checkpoint, or written before checkpoints had a format version",
+            ));
+        }
+        let version = read_u32(reader)?;
+        if version != CHECKPOINT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported checkpoint version {version}, expected {CHECKPOINT_VERSION}"),
+            ));
+        }
+        let iteration = read_u64(reader)?;
+        let time = read_float(reader)?;
+        let count = read_u64(reader)?;
+        let mut state = State::new();
+        for _ in 0..count {
+            let id = read_u64(reader)? as ID;
+            let mut vectors = [Vector2::zeros(); 6];
+            for v in vectors.iter_mut() {
+                *v = Vector2::new(read_float(reader)?, read_float(reader)?);
+            }
+            let radius = read_float(reader)?;
+            let angular_velocity = read_float(reader)?;
+            let [position, velocity, r2, r3, r4, r5] = vectors;
+            let ball = Ball {
+                id,
+                position,
+                velocity,
+                radius,
+                angular_velocity,
+            };
+            state.insert(id, (ball, [r2, r3, r4, r5]));
+        }
+        Ok(Self {
+            iteration,
+            time,
+            state,
+        })
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
 
 fn did_ball_go_outside(ball: &Ball, config: &InputData) -> Vec<Wall> {
     let mut collisions = Vec::with_capacity(2);
@@ -97,9 +548,47 @@ fn did_ball_go_outside(ball: &Ball, config: &InputData) -> Vec<Wall> {
     collisions
 }
 
+fn find_hole(ball: &Ball, holes: &[Hole]) -> Option<usize> {
+    holes.iter().position(|hole| {
+        (hole.position - ball.position).magnitude_squared() <= (hole.radius + ball.radius).powi(2)
+    })
+}
+
+/// Force on `b` from `other`, pushing it away from `other` proportionally to their overlap.
 fn calculate_force(b: &Ball, other: &Ball, radius_sum: Float) -> Vector2<Float> {
-    let r_hat = (other.position - b.position).normalize();
-    K * ((b.position - other.position).magnitude() - radius_sum) * r_hat
+    let delta = b.position - other.position;
+    let overlap = radius_sum - delta.magnitude();
+    // Balls exactly on top of each other have no direction to be pushed in, so any will do.
+    let normal = delta.try_normalize(0.0).unwrap_or_else(Vector2::x);
+    K * overlap * normal
+}
+
+/// Velocity of the point of `b`'s surface at `arm` from its center.
+fn surface_velocity(b: &Ball, arm: Vector2<Float>) -> Vector2<Float> {
+    b.velocity + b.angular_velocity * Vector2::new(-arm.y, arm.x)
+}
+
+/// Friction on `b` where it touches a surface moving at `surface_velocity_at_contact`, with
+/// `normal` pointing from the contact to the center of `b`, and the torque it makes on `b`. It
+/// opposes the sliding between both surfaces, up to `CONTACT_FRICTION` times the force pushing
+/// them apart.
+fn contact_friction(
+    b: &Ball,
+    normal: Vector2<Float>,
+    normal_force: Float,
+    surface_velocity_at_contact: Vector2<Float>,
+) -> (Vector2<Float>, Float) {
+    let arm = -normal * b.radius;
+    let sliding = surface_velocity(b, arm) - surface_velocity_at_contact;
+    let tangential_sliding = sliding - normal * sliding.dot(&normal);
+    let force = -TANGENTIAL_DAMPING * tangential_sliding;
+    let max_force = CONTACT_FRICTION * normal_force;
+    let force = if force.magnitude() > max_force {
+        force * (max_force / force.magnitude())
+    } else {
+        force
+    };
+    (force, arm.perp(&force))
 }
 
 trait PredictorFromBall: Sized {
@@ -126,203 +615,810 @@ impl PredictorFromBall for GearPredictor<Vector2<f64>> {
     }
 }
 
-fn run<W: Write, F: FnMut(&BTreeMap<ID, (Ball, [Vector2<f64>; 4])>, Float) -> bool>(
-    config: InputData,
-    mut output_writer: W,
-    mut stop_condition: F,
+#[derive(Default)]
+struct StepBuffers {
+    predictions: BTreeMap<ID, GearCorrector<Vector2<Float>>>,
+    predicted_balls: Vec<Ball>,
+    forces: HashMap<ID, Vector2<Float>>,
+    /// Only filled with `--with-spin`, balls without an entry have no torque.
+    torques: HashMap<ID, Float>,
+}
+
+/// Adds the forces acting on each ball to `forces`, which must have an entry for every ball. With
+/// `--with-spin` it also adds the torques from the friction at the contacts to `torques`.
+fn calculate_forces(
+    balls: &[Ball],
+    config: &InputData,
+    forces: &mut HashMap<ID, Vector2<Float>>,
+    torques: &mut HashMap<ID, Float>,
 ) {
-    let mut time = 0.0;
-    let mut state: BTreeMap<_, _> = config
-        .simple_input_data
-        .balls
+    let radius_sum = config.simple_input_data.ball_radius * 2.0;
+    // Also finds the pairs that can touch before the next step. A whole step is enough even when
+    // it is split in substeps.
+    let max_speed = balls
         .iter()
-        .copied()
-        .map(|p| (p.id, (p, [Vector2::zeros(); 4])))
-        .collect();
+        .map(|ball| ball.velocity.magnitude())
+        .fold(0.0, Float::max);
+    let interaction_radius = approach_margin(max_speed, config.delta_time());
 
-    let holes = if config.with_holes {
-        HOLE_POSITIONS
-            .map(|v| {
-                v.component_mul(&Vector2::new(
-                    config.simple_input_data.table_width,
-                    config.simple_input_data.table_height,
-                ))
-            })
-            .to_vec()
+    // The grid only pays off with many balls.
+    let neighbors = if balls.len() < CIM_MIN_BALLS {
+        SimpleNeighborFinder::find_neighbors(
+            balls,
+            cim::simple_finder::SystemInfo {
+                cyclic: false,
+                interaction_radius,
+                space_width: config.simple_input_data.table_width,
+                space_height: config.simple_input_data.table_height,
+                max_neighbors: None,
+            },
+        )
     } else {
-        vec![]
+        let system = cim::cim_finder::SystemInfo {
+            cyclic: false,
+            interaction_radius,
+            space_width: config.simple_input_data.table_width,
+            space_height: config.simple_input_data.table_height,
+            columns: cim::cim_finder::SystemInfo::cells_along(
+                config.simple_input_data.table_width,
+                interaction_radius,
+                config.simple_input_data.ball_radius,
+            ),
+            rows: cim::cim_finder::SystemInfo::cells_along(
+                config.simple_input_data.table_height,
+                interaction_radius,
+                config.simple_input_data.ball_radius,
+            ),
+            max_neighbors: None,
+        };
+        debug_assert!(system.is_grid_wide_enough(config.simple_input_data.ball_radius));
+        CimNeighborFinder::find_neighbors(balls, system)
     };
 
-    if match &config.output_condition {
-        OutputCondition::Every(..) => true,
-        OutputCondition::WhenBallCountHits { counts } => {
-            counts.iter().any(|&count| state.len() <= count)
+    let balls_by_id: HashMap<ID, &Ball> = balls.iter().map(|b| (b.id, b)).collect();
+
+    for ball in balls {
+        let neighs = neighbors.get_neighbors(ball.id);
+
+        for other in neighs
+            .filter(|other_id| ball.id > **other_id)
+            .map(|id| balls_by_id[id])
+        {
+            // Neighbors that are only about to touch don't push each other yet.
+            if (ball.position - other.position).magnitude() > radius_sum {
+                continue;
+            }
+            let force = calculate_force(ball, other, radius_sum);
+            *forces.get_mut(&ball.id).unwrap() += force;
+            *forces.get_mut(&other.id).unwrap() -= force;
+
+            if config.with_spin {
+                let normal = (ball.position - other.position)
+                    .try_normalize(0.0)
+                    .unwrap_or_else(Vector2::x);
+                let other_arm = normal * other.radius;
+                let (friction, torque) = contact_friction(
+                    ball,
+                    normal,
+                    force.dot(&normal).max(0.0),
+                    surface_velocity(other, other_arm),
+                );
+                *forces.get_mut(&ball.id).unwrap() += friction;
+                *forces.get_mut(&other.id).unwrap() -= friction;
+                *torques.entry(ball.id).or_default() += torque;
+                *torques.entry(other.id).or_default() += other_arm.perp(&-friction);
+            }
         }
-    } {
-        // Write to output
-        IterableFrame {
-            time,
-            balls: state.values().map(|(b, _)| b),
+
+        let walls = did_ball_go_outside(ball, config);
+        for wall in walls {
+            let (normal, depth) = match wall {
+                Wall::Left => (Vector2::x(), -(ball.position.x - ball.radius)),
+                Wall::Right => (
+                    -Vector2::x(),
+                    ball.position.x - config.simple_input_data.table_width + ball.radius,
+                ),
+                Wall::Bottom => (Vector2::y(), -(ball.position.y - ball.radius)),
+                Wall::Top => (
+                    -Vector2::y(),
+                    ball.position.y - config.simple_input_data.table_height + ball.radius,
+                ),
+            };
+            *forces.get_mut(&ball.id).unwrap() += K * depth * normal;
+
+            if config.with_spin {
+                let (friction, torque) =
+                    contact_friction(ball, normal, K * depth, Vector2::zeros());
+                *forces.get_mut(&ball.id).unwrap() += friction;
+                *torques.entry(ball.id).or_default() += torque;
+            }
+        }
+    }
+}
+
+/// Fills `buffers.forces` and `buffers.torques` with the forces on the balls where they are, without
+/// moving them.
+fn current_forces(state: &State, config: &InputData, buffers: &mut StepBuffers) {
+    buffers.predicted_balls.clear();
+    buffers
+        .predicted_balls
+        .extend(state.values().map(|(ball, _)| *ball));
+
+    buffers.forces.clear();
+    buffers
+        .forces
+        .extend(state.iter().map(|(&k, _)| (k, Vector2::zeros())));
+    buffers.torques.clear();
+
+    calculate_forces(
+        &buffers.predicted_balls,
+        config,
+        &mut buffers.forces,
+        &mut buffers.torques,
+    );
+}
+
+/// Sets the acceleration velocity Verlet and Beeman keep in `r2` from the forces where the balls
+/// start, so the first step doesn't begin from rest. Beeman also takes it as the acceleration
+/// before that.
+fn initialize_accelerations(state: &mut State, config: &InputData, buffers: &mut StepBuffers) {
+    current_forces(state, config, buffers);
+    for (id, (_, [r2, r3, ..])) in state.iter_mut() {
+        let acceleration = buffers.forces[id] / config.simple_input_data.ball_mass;
+        *r2 = acceleration;
+        if let Integrator::Beeman = config.integrator {
+            *r3 = acceleration;
         }
-        .write_to(&mut output_writer)
-        .unwrap();
     }
+}
 
-    let delta_time = (10.0 as Float).powi(-(config.delta_time_n as i32));
-    let mut iteration = 0;
+fn step(state: &mut State, config: &InputData, delta_time: Float, buffers: &mut StepBuffers) {
+    let restitution = config.simple_input_data.restitution;
+    let walls_before = if restitution < 1.0 {
+        state
+            .values()
+            .map(|(ball, _)| did_ball_go_outside(ball, config))
+            .collect_vec()
+    } else {
+        vec![]
+    };
 
-    let mut predictions = BTreeMap::new();
-    let mut predicted_balls = Vec::new();
-    let mut forces = HashMap::new();
+    buffers.torques.clear();
+    match config.integrator {
+        Integrator::Gear => gear_step(state, config, delta_time, buffers),
+        Integrator::VelocityVerlet => velocity_verlet_step(state, config, delta_time, buffers),
+        Integrator::Beeman => beeman_step(state, config, delta_time, buffers),
+    }
 
-    while !stop_condition(&state, time) {
-        let radius_sum = config.simple_input_data.ball_radius * 2.0;
+    if config.with_spin {
+        // Solid spheres.
+        let moment_of_inertia =
+            0.4 * config.simple_input_data.ball_mass * config.simple_input_data.ball_radius.powi(2);
+        for (id, (ball, _)) in state.iter_mut() {
+            let torque = buffers.torques.get(id).copied().unwrap_or(0.0);
+            ball.angular_velocity += torque / moment_of_inertia * delta_time;
+        }
+    }
+
+    let slowdown = config.simple_input_data.friction * delta_time;
+    if slowdown > 0.0 {
+        for (ball, _) in state.values_mut() {
+            let speed = ball.velocity.magnitude();
+            // Slow balls stop instead of going backwards.
+            ball.velocity *= if speed > slowdown {
+                1.0 - slowdown / speed
+            } else {
+                0.0
+            };
+        }
+    }
+
+    // The cushions are elastic springs, so the energy is taken away once the ball has been pushed
+    // completely back inside the table. Doing it earlier could leave the ball stuck in the cushion.
+    for ((ball, _), before) in state.values_mut().zip(walls_before) {
+        let after = did_ball_go_outside(ball, config);
+        for wall in before.into_iter().filter(|wall| !after.contains(wall)) {
+            match wall {
+                Wall::Left | Wall::Right => ball.velocity.x *= restitution,
+                Wall::Top | Wall::Bottom => ball.velocity.y *= restitution,
+            }
+        }
+    }
+}
 
-        predictions.clear();
-        predictions.extend(state.iter().map(|(&id, (b, [r2, r3, r4, r5]))| {
+fn gear_step(state: &mut State, config: &InputData, delta_time: Float, buffers: &mut StepBuffers) {
+    buffers.predictions.clear();
+    buffers
+        .predictions
+        .extend(state.iter().map(|(&id, (b, [r2, r3, r4, r5]))| {
             (
                 id,
                 GearPredictor::from_ball(b, *r2, *r3, *r4, *r5).predict(delta_time),
             )
         }));
 
-        predicted_balls.clear();
-        predicted_balls.extend(predictions.iter().map(|(&id, pred)| Ball {
+    buffers.predicted_balls.clear();
+    buffers
+        .predicted_balls
+        .extend(buffers.predictions.iter().map(|(&id, pred)| Ball {
             id,
             radius: state[&id].0.radius,
             position: pred.predictions[0],
             velocity: pred.predictions[1],
+            angular_velocity: state[&id].0.angular_velocity,
         }));
 
-        let neighbors = SimpleNeighborFinder::find_neighbors(
-            &predicted_balls,
-            cim::simple_finder::SystemInfo {
-                cyclic: false,
-                interaction_radius: 0.0,
-                space_width: config.simple_input_data.table_width,
-                space_height: config.simple_input_data.table_height,
-            },
-        );
+    buffers.forces.clear();
+    buffers
+        .forces
+        .extend(state.iter().map(|(&k, _)| (k, Vector2::zeros())));
 
-        /*
-         * It's slower for normal ball count but faster for more balls
-        let neighbors = CimNeighborFinder::find_neighbors(
-            &state.values().cloned().collect_vec(),
-            cim::cim_finder::SystemInfo {
-                cyclic: false,
-                interaction_radius: 0.0,
-                space_width: config.simple_input_data.table_width,
-                space_height: config.simple_input_data.table_height,
-                columns: (config.simple_input_data.table_width / radius_sum).floor() as usize,
-                rows: (config.simple_input_data.table_height / radius_sum).floor() as usize,
-            },
-        );
-        */
+    calculate_forces(
+        &buffers.predicted_balls,
+        config,
+        &mut buffers.forces,
+        &mut buffers.torques,
+    );
 
-        forces.clear();
-        forces.extend(state.iter().map(|(&k, _)| (k, Vector2::zeros())));
+    for (id, (ball, higher_order)) in state.iter_mut() {
+        let force = buffers
+            .forces
+            .get(id)
+            .cloned()
+            .unwrap_or_else(Vector2::zeros);
+        let acceleration = force / config.simple_input_data.ball_mass;
+        let [p, v, r2, r3, r4, r5] = buffers.predictions[id].correct(acceleration, delta_time);
+        ball.position = p;
+        ball.velocity = v;
+        *higher_order = [r2, r3, r4, r5];
+    }
+}
 
-        let get_predicted_ball = |corrector: &GearCorrector<_>, original_ball: &Ball| {
-            let &Ball { id, radius, .. } = original_ball;
-            let &GearCorrector {
-                predictions: [position, velocity, ..],
-            } = corrector;
+/// The acceleration of the previous step is kept where the Gear integrator keeps `r2`.
+fn velocity_verlet_step(
+    state: &mut State,
+    config: &InputData,
+    delta_time: Float,
+    buffers: &mut StepBuffers,
+) {
+    buffers.predicted_balls.clear();
+    buffers
+        .predicted_balls
+        .extend(state.values().map(|(ball, [acceleration, ..])| Ball {
+            position: velocity_verlet_position(
+                ball.position,
+                ball.velocity,
+                *acceleration,
+                delta_time,
+            ),
+            ..*ball
+        }));
+
+    buffers.forces.clear();
+    buffers
+        .forces
+        .extend(state.iter().map(|(&k, _)| (k, Vector2::zeros())));
+
+    calculate_forces(
+        &buffers.predicted_balls,
+        config,
+        &mut buffers.forces,
+        &mut buffers.torques,
+    );
+
+    // Both are in id order.
+    for ((id, (ball, [acceleration, ..])), moved) in
+        state.iter_mut().zip(buffers.predicted_balls.iter())
+    {
+        let new_acceleration = buffers.forces[id] / config.simple_input_data.ball_mass;
+        ball.position = moved.position;
+        ball.velocity =
+            velocity_verlet_velocity(ball.velocity, *acceleration, new_acceleration, delta_time);
+        *acceleration = new_acceleration;
+    }
+}
+
+/// The acceleration of the previous step is kept where the Gear integrator keeps `r2`, and the one
+/// before it where it keeps `r3`.
+fn beeman_step(
+    state: &mut State,
+    config: &InputData,
+    delta_time: Float,
+    buffers: &mut StepBuffers,
+) {
+    buffers.predicted_balls.clear();
+    buffers
+        .predicted_balls
+        .extend(state.values().map(|(ball, [acceleration, prev_acc, ..])| {
             Ball {
-                id,
-                radius,
-                position,
-                velocity,
+                position: BeemanState {
+                    prev_acc: *prev_acc,
+                }
+                .predict_position(
+                    ball.position,
+                    ball.velocity,
+                    *acceleration,
+                    delta_time,
+                ),
+                ..*ball
             }
+        }));
+
+    buffers.forces.clear();
+    buffers
+        .forces
+        .extend(state.iter().map(|(&k, _)| (k, Vector2::zeros())));
+
+    calculate_forces(
+        &buffers.predicted_balls,
+        config,
+        &mut buffers.forces,
+        &mut buffers.torques,
+    );
+
+    // Both are in id order.
+    for ((id, (ball, [acceleration, prev_acc, ..])), moved) in
+        state.iter_mut().zip(buffers.predicted_balls.iter())
+    {
+        let new_acceleration = buffers.forces[id] / config.simple_input_data.ball_mass;
+        let mut beeman = BeemanState {
+            prev_acc: *prev_acc,
         };
+        ball.position = moved.position;
+        ball.velocity =
+            beeman.correct_velocity(ball.velocity, *acceleration, new_acceleration, delta_time);
+        *prev_acc = beeman.prev_acc;
+        *acceleration = new_acceleration;
+    }
+}
 
-        for (id, ball) in predictions
-            .iter()
-            .map(|(&id, corrector)| (id, get_predicted_ball(corrector, &state[&id].0)))
-        {
-            let neighs = neighbors.get_neighbors(id);
-
-            for other in neighs
-                .filter(|other_id| id > **other_id)
-                .map(|id| get_predicted_ball(&predictions[id], &state[id].0))
-            {
-                let force = calculate_force(&ball, &other, radius_sum);
-                *forces.get_mut(&ball.id).unwrap() += force;
-                *forces.get_mut(&other.id).unwrap() -= force;
+/// Moves every ball up to the next collision and resolves it as a hard sphere collision. Returns
+/// the time it took and the ball pocketed in it, if any, or `None` if nothing will ever collide.
+fn event_step(
+    state: &mut State,
+    config: &InputData,
+    holes: &[Hole],
+) -> Option<(Float, Option<(ID, usize)>)> {
+    let collision = find_earliest_collision(
+        &state.values().map(|(ball, _)| ball).collect_vec(),
+        holes,
+        &config.simple_input_data,
+    )?;
+
+    let mut balls: BTreeMap<ID, Ball> = state
+        .iter()
+        .map(|(&id, (ball, _))| (id, ball.advance(collision.time)))
+        .collect();
+    apply_collision(&mut balls, &config.simple_input_data, collision);
+
+    state.retain(|id, _| balls.contains_key(id));
+    for (id, (ball, _)) in state.iter_mut() {
+        *ball = balls[id];
+    }
+
+    let pocketed = match collision.info {
+        CollisionAgainst::Hole(id, hole) => Some((id, hole)),
+        CollisionAgainst::Ball(..) | CollisionAgainst::Wall(..) => None,
+    };
+    Some((collision.time, pocketed))
+}
+
+/// Amount of substeps the next step has to be split into so that no ball moves more than
+/// `max_displacement` times its radius in a single substep. The movement is estimated from the
+/// velocity and the acceleration, which every integrator keeps in `r2`.
+fn substeps_needed(state: &State, delta_time: Float, max_displacement: Float) -> u32 {
+    let mut substeps = 1;
+    while substeps < MAX_SUBSTEPS
+        && state.values().any(|(ball, [acceleration, ..])| {
+            let substep = delta_time / substeps as Float;
+            let displacement = ball.velocity * substep + acceleration * substep.powi(2) / 2.0;
+            displacement.magnitude() > max_displacement * ball.radius
+        })
+    {
+        substeps *= 2;
+    }
+    substeps
+}
+
+/// Moves overlapping balls apart, and balls overlapping the cushions back inside the table, until
+/// no overlap is bigger than the tolerance or the iterations run out. Velocities are not changed.
+fn relax_contacts(state: &mut State, config: &InputData) {
+    let SimpleInputData {
+        table_width,
+        table_height,
+        ..
+    } = config.simple_input_data;
+
+    for _ in 0..config.contact_iterations {
+        let mut max_overlap: Float = 0.0;
+
+        let ids = state.keys().copied().collect_vec();
+        for (&id1, &id2) in ids.iter().tuple_combinations() {
+            let (b1, b2) = (state[&id1].0, state[&id2].0);
+            let delta = b2.position - b1.position;
+            let overlap = b1.radius + b2.radius - delta.magnitude();
+            if overlap > 0.0 {
+                max_overlap = max_overlap.max(overlap);
+                let correction =
+                    delta.try_normalize(0.0).unwrap_or_else(Vector2::x) * overlap / 2.0;
+                state.get_mut(&id1).unwrap().0.position -= correction;
+                state.get_mut(&id2).unwrap().0.position += correction;
             }
+        }
 
-            let walls = did_ball_go_outside(&ball, &config);
-            for wall in walls {
-                match wall {
-                    Wall::Left => {
-                        let depth = -(ball.position.x - ball.radius);
-                        forces.get_mut(&ball.id).unwrap().x += K * depth;
-                    }
-                    Wall::Right => {
-                        let depth =
-                            ball.position.x - config.simple_input_data.table_width + ball.radius;
-                        forces.get_mut(&ball.id).unwrap().x -= K * depth;
-                    }
-                    Wall::Bottom => {
-                        let depth = -(ball.position.y - ball.radius);
-                        forces.get_mut(&ball.id).unwrap().y += K * depth;
+        for (ball, _) in state.values_mut() {
+            let clamped = clamp_into_table(ball.position, ball.radius, table_width, table_height);
+            max_overlap = max_overlap.max((clamped - ball.position).amax());
+            ball.position = clamped;
+        }
+
+        if max_overlap <= config.contact_tolerance {
+            break;
+        }
+    }
+}
+
+/// Writes the parameters the simulation will actually run with, after applying the arguments.
+fn write_config<W: Write>(writer: &mut W, config: &InputData) -> io::Result<()> {
+    let SimpleInputData {
+        table_width,
+        table_height,
+        hole_radius,
+        ball_radius,
+        ball_mass,
+        restitution,
+        friction,
+        ref hole_positions,
+        ref balls,
+    } = config.simple_input_data;
+    writeln!(writer, "table: {table_width} x {table_height}")?;
+    writeln!(writer, "hole radius: {hole_radius}")?;
+    if config.with_holes {
+        writeln!(
+            writer,
+            "side hole radius: {}",
+            config.side_hole_radius.unwrap_or(hole_radius)
+        )?;
+        writeln!(writer, "holes: {}", hole_positions.len())?;
+    } else {
+        writeln!(writer, "holes: disabled")?;
+    }
+    writeln!(writer, "ball radius: {ball_radius}")?;
+    writeln!(writer, "ball mass: {ball_mass}")?;
+    writeln!(writer, "balls: {}", balls.len())?;
+    writeln!(writer, "k: {K}")?;
+    writeln!(writer, "restitution: {restitution}")?;
+    writeln!(writer, "friction: {friction}")?;
+    if config.with_spin {
+        writeln!(
+            writer,
+            "spin: contact friction {CONTACT_FRICTION}, tangential damping {TANGENTIAL_DAMPING}"
+        )?;
+    }
+    if config.event_driven {
+        writeln!(writer, "integrator: event driven")?;
+    } else {
+        writeln!(writer, "integrator: {:?}", config.integrator)?;
+    }
+    if config.contact_iterations > 0 {
+        writeln!(
+            writer,
+            "contact relaxation: {} iterations, tolerance {}",
+            config.contact_iterations, config.contact_tolerance
+        )?;
+    }
+    writeln!(
+        writer,
+        "delta time: 1e-{} ({})",
+        config.delta_time_n,
+        config.delta_time()
+    )?;
+    if let Some(max_displacement) = config.max_displacement {
+        writeln!(writer, "max displacement: {max_displacement}")?;
+    }
+    writeln!(writer, "output condition: {:?}", config.output_condition)?;
+    writeln!(writer, "output format: {:?}", config.output_format)?;
+    writeln!(writer, "output time scale: {}", config.time_scale)?;
+    if let Some(every) = config.checkpoint_every {
+        writeln!(
+            writer,
+            "checkpoint: every {every} steps to {}",
+            config.checkpoint_file.display()
+        )?;
+    }
+    if let Some(Checkpoint {
+        iteration, time, ..
+    }) = &config.restore
+    {
+        writeln!(writer, "restored from step {iteration} (t = {time})")?;
+    }
+    Ok(())
+}
+
+fn write_frame<W: Write>(
+    config: &InputData,
+    output_writer: &mut W,
+    observers: &mut [Box<dyn SimulationObserver>],
+    state: &State,
+    time: Float,
+) -> io::Result<()> {
+    let scaled_time = time * config.time_scale;
+    let balls = state.values().map(|(b, _)| b);
+    match config.output_format {
+        // Only the text output has room for the spin.
+        OutputFormat::Text if config.with_spin => {
+            let balls = balls.map(WithSpin).collect_vec();
+            simulation::frame::write_frame(output_writer, scaled_time, balls.iter())?;
+        }
+        format => IterableFrame {
+            time: scaled_time,
+            balls,
+        }
+        .write_formatted(format, output_writer)?,
+    }
+    for observer in observers.iter_mut() {
+        observer.on_frame(state, time);
+    }
+    Ok(())
+}
+
+struct PoolSimulation {
+    config: InputData,
+    observers: Vec<Box<dyn SimulationObserver>>,
+    holes: Vec<Hole>,
+    delta_time: Float,
+    iteration: u64,
+    time: Float,
+    state: State,
+    last_output_time: Float,
+    buffers: StepBuffers,
+    pocket_times: Vec<(ID, Float)>,
+    /// Balls pocketed in the last step, `None` until a step is made.
+    pocketed: Option<Vec<(ID, usize)>>,
+    started: bool,
+    /// Set when the event driven simulation runs out of events.
+    out_of_events: bool,
+}
+
+impl PoolSimulation {
+    fn new(mut config: InputData, observers: Vec<Box<dyn SimulationObserver>>) -> Self {
+        let restored = config.restore.is_some();
+        let Checkpoint {
+            iteration,
+            time,
+            mut state,
+        } = config.restore.take().unwrap_or_else(|| Checkpoint {
+            iteration: 0,
+            time: 0.0,
+            state: config
+                .simple_input_data
+                .balls
+                .iter()
+                .copied()
+                .map(|p| (p.id, (p, [Vector2::zeros(); 4])))
+                .collect(),
+        });
+
+        let holes = if config.with_holes {
+            let SimpleInputData {
+                table_width,
+                table_height,
+                hole_radius,
+                ref hole_positions,
+                ..
+            } = config.simple_input_data;
+            table_holes(
+                table_width,
+                table_height,
+                hole_positions,
+                hole_radius,
+                config.side_hole_radius.unwrap_or(hole_radius),
+            )
+        } else {
+            vec![]
+        };
+
+        let delta_time = config.delta_time();
+
+        let mut buffers = StepBuffers::default();
+        // Gear doesn't need them, a checkpoint already has them, and hard spheres have no forces.
+        let integrator_needs_them = matches!(
+            config.integrator,
+            Integrator::VelocityVerlet | Integrator::Beeman
+        );
+        if integrator_needs_them && !restored && !config.event_driven {
+            initialize_accelerations(&mut state, &config, &mut buffers);
+        }
+
+        Self {
+            config,
+            observers,
+            holes,
+            delta_time,
+            iteration,
+            time,
+            state,
+            last_output_time: time,
+            buffers,
+            pocket_times: vec![],
+            pocketed: None,
+            started: false,
+            out_of_events: false,
+        }
+    }
+
+    fn write_frame<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        write_frame(
+            &self.config,
+            writer,
+            &mut self.observers,
+            &self.state,
+            self.time,
+        )
+    }
+}
+
+impl Simulation for PoolSimulation {
+    type State = State;
+    type Time = Float;
+
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn time(&self) -> Float {
+        self.time
+    }
+
+    // Without balls nothing else can happen.
+    fn is_finished(&self) -> bool {
+        self.state.is_empty() || self.out_of_events
+    }
+
+    fn step(&mut self) {
+        let config = &self.config;
+        let pocketed = if config.event_driven {
+            // Every event counts as a step.
+            let Some((elapsed, pocketed)) = event_step(&mut self.state, config, &self.holes) else {
+                self.out_of_events = true;
+                return;
+            };
+            self.iteration += 1;
+            self.time += elapsed;
+            pocketed.into_iter().collect_vec()
+        } else {
+            match config.max_displacement {
+                Some(max_displacement) => {
+                    let substeps = substeps_needed(&self.state, self.delta_time, max_displacement);
+                    if substeps > 1 {
+                        eprintln!("t = {}: splitting step into {substeps} substeps", self.time);
                     }
-                    Wall::Top => {
-                        let depth =
-                            ball.position.y - config.simple_input_data.table_height + ball.radius;
-                        forces.get_mut(&ball.id).unwrap().y -= K * depth;
+                    for _ in 0..substeps {
+                        step(
+                            &mut self.state,
+                            config,
+                            self.delta_time / substeps as Float,
+                            &mut self.buffers,
+                        );
                     }
                 }
+                None => step(&mut self.state, config, self.delta_time, &mut self.buffers),
             }
+            relax_contacts(&mut self.state, config);
+
+            let holes = &self.holes;
+            let pocketed = self
+                .state
+                .drain_filter(|_, (ball, _)| find_hole(ball, holes).is_some())
+                .map(|(id, (ball, _))| (id, find_hole(&ball, holes).unwrap()))
+                .collect_vec();
+
+            self.iteration += 1;
+            self.time = self.iteration as f64 * self.delta_time;
+            pocketed
+        };
+        self.pocket_times
+            .extend(pocketed.iter().map(|&(id, _)| (id, self.time)));
+
+        if config
+            .checkpoint_every
+            .is_some_and(|every| self.iteration % every == 0)
+        {
+            save_checkpoint(
+                &config.checkpoint_file,
+                self.iteration,
+                self.time,
+                &self.state,
+            )
+            .unwrap();
         }
 
-        for (id, (ball, higher_order)) in state.iter_mut() {
-            let force = forces.get(id).cloned().unwrap_or_else(Vector2::zeros);
-            let acceleration = force / config.simple_input_data.ball_mass;
-            let [p, v, r2, r3, r4, r5] = predictions[id].correct(acceleration, delta_time);
-            ball.position = p;
-            ball.velocity = v;
-            *higher_order = [r2, r3, r4, r5];
+        self.pocketed = Some(pocketed);
+    }
+
+    fn emit<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            // Hard spheres have no forces.
+            if !self.config.event_driven {
+                current_forces(&self.state, &self.config, &mut self.buffers);
+            }
+            for observer in self.observers.iter_mut() {
+                observer.on_forces(&self.buffers.forces, self.time);
+            }
+            if match &self.config.output_condition {
+                OutputCondition::Every(..) | OutputCondition::EverySeconds(..) => true,
+                OutputCondition::WhenBallCountHits { counts } => {
+                    counts.iter().any(|&count| self.state.len() <= count)
+                }
+                OutputCondition::PocketEvents => false,
+            } {
+                self.write_frame(writer)?;
+            }
+            return Ok(());
         }
 
-        let removed_balls = state
-            .drain_filter(|_, (ball, _)| {
-                holes.iter().any(|hole| {
-                    (hole - ball.position).magnitude_squared()
-                        <= (config.simple_input_data.hole_radius + ball.radius).powi(2)
-                })
-            })
-            .count();
+        // Nothing happened since the last call.
+        let Some(pocketed) = self.pocketed.take() else {
+            return Ok(());
+        };
+        let removed_balls = pocketed.len();
 
-        iteration += 1;
-        time = iteration as f64 * delta_time;
+        if let OutputCondition::PocketEvents = self.config.output_condition {
+            for &(id, hole) in &pocketed {
+                let kind = self.holes[hole].kind;
+                let time = self.time * self.config.time_scale;
+                writer.write_fmt(format_args!("{time} {id} {hole} {kind}\n"))?;
+            }
+        }
 
-        if match &config.output_condition {
-            OutputCondition::Every(EveryArgs { steps, .. }) => iteration % steps == 0,
-            OutputCondition::WhenBallCountHits { counts } => counts
-                .iter()
-                .any(|&count| state.len() <= count && state.len() + removed_balls > count),
-        } {
-            // Write to output
-            IterableFrame {
-                time,
-                balls: state.values().map(|(b, _)| b),
+        let time = self.time;
+        // Before the frame so that it's logged with everything that happened in this step.
+        for observer in self.observers.iter_mut() {
+            observer.on_forces(&self.buffers.forces, time);
+            observer.on_step(&self.state, time);
+        }
+        if match &self.config.output_condition {
+            OutputCondition::Every(EveryArgs { steps, .. }) => self.iteration % steps == 0,
+            OutputCondition::EverySeconds(EverySecondsArgs { seconds, .. }) => {
+                (time / seconds).floor() > (self.last_output_time / seconds).floor()
             }
-            .write_to(&mut output_writer)
-            .unwrap();
+            OutputCondition::WhenBallCountHits { counts } => counts.iter().any(|&count| {
+                self.state.len() <= count && self.state.len() + removed_balls > count
+            }),
+            OutputCondition::PocketEvents => false,
+        } {
+            self.write_frame(writer)?;
+            self.last_output_time = time;
         }
+        Ok(())
     }
 
-    // Write last frame in case it wasnt
-    if match config.output_condition {
-        OutputCondition::Every(EveryArgs { steps, last }) => last && iteration % steps != 0,
-        OutputCondition::WhenBallCountHits { .. } => false,
-    } {
-        IterableFrame {
-            time,
-            balls: state.values().map(|(b, _)| b),
+    fn finish<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        // Write last frame in case it wasnt
+        if match self.config.output_condition {
+            OutputCondition::Every(EveryArgs { steps, last }) => {
+                last && self.iteration % steps != 0
+            }
+            OutputCondition::EverySeconds(EverySecondsArgs { last, .. }) => {
+                last && self.last_output_time != self.time
+            }
+            OutputCondition::WhenBallCountHits { .. } | OutputCondition::PocketEvents => false,
+        } {
+            self.write_frame(writer)?;
         }
-        .write_to(&mut output_writer)
-        .unwrap();
+
+        if self.config.with_holes {
+            eprintln!(
+                "Pocketed {} balls: {}",
+                self.pocket_times.len(),
+                self.pocket_times
+                    .iter()
+                    .map(|(id, time)| format!("{id} at t = {time}"))
+                    .join(", ")
+            );
+        }
+        Ok(())
     }
 }
 
@@ -330,27 +1426,762 @@ fn main() {
     let args = Arguments::parse();
 
     let input = fs::read_to_string(args.input).unwrap();
+    let simple_input_data = if args.polar_input {
+        polar_input_parser().parse(&input).into_result()
+    } else {
+        input_parser().parse(&input).into_result()
+    }
+    .expect("Error parsing input data.");
+    if args.with_holes {
+        for warning in simple_input_data.warnings(args.side_hole_radius) {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    if args.event_driven && simple_input_data.friction != 0.0 {
+        eprintln!("Warning: the event driven simulation ignores the table friction");
+    }
+    let mut observers: Vec<Box<dyn SimulationObserver>> = vec![];
+    // Piped stderr stays clean unless asked for.
+    if let Some(max_duration) = args
+        .max_duration
+        .filter(|_| args.progress || io::stderr().is_terminal())
+    {
+        observers.push(Box::new(ProgressObserver {
+            max_duration,
+            last_percent: 0,
+            start: None,
+        }));
+    }
+    if args.check_tunneling {
+        observers.push(Box::new(TunnelingChecker {
+            ball_radius: simple_input_data.ball_radius,
+            table_width: simple_input_data.table_width,
+            table_height: simple_input_data.table_height,
+            // The positions before a restored checkpoint are unknown, so its first step is skipped.
+            last_positions: if args.restore.is_some() {
+                BTreeMap::new()
+            } else {
+                simple_input_data
+                    .balls
+                    .iter()
+                    .map(|ball| (ball.id, ball.position))
+                    .collect()
+            },
+        }));
+    }
+    if let Some(path) = &args.energy_output {
+        let mut writer = File::create(path).unwrap();
+        writer
+            .write_all(b"time,kinetic_energy,ball_count\n")
+            .unwrap();
+        observers.push(Box::new(EnergyLogger {
+            writer,
+            ball_mass: simple_input_data.ball_mass,
+            time_scale: args.time_scale,
+        }));
+    }
+    if let Some(path) = &args.metrics_output {
+        let mut writer = File::create(path).unwrap();
+        writer
+            .write_all(b"time,steps,wall_time,ball_count,max_speed\n")
+            .unwrap();
+        observers.push(Box::new(MetricsLogger {
+            writer,
+            time_scale: args.time_scale,
+            steps: 0,
+            start: Instant::now(),
+        }));
+    }
+    if let Some(path) = &args.force_output {
+        observers.push(Box::new(ForceLogger {
+            writer: BufWriter::new(File::create(path).unwrap()),
+            last_forces: HashMap::new(),
+            time_scale: args.time_scale,
+        }));
+    }
+
     let input = InputData {
-        simple_input_data: input_parser()
-            .parse(&input)
-            .into_result()
-            .expect("Error parsing input data."),
+        simple_input_data,
         delta_time_n: args.delta_time_n,
         with_holes: args.with_holes,
+        side_hole_radius: args.side_hole_radius,
+        output_format: args.output_format,
+        max_displacement: args.max_displacement,
+        checkpoint_every: args.checkpoint_every,
+        checkpoint_file: args.checkpoint_file,
+        restore: args
+            .restore
+            .map(|path| Checkpoint::load(&path).expect("Error reading checkpoint.")),
         output_condition: args.output_condition,
+        time_scale: args.time_scale,
+        contact_iterations: args.contact_iterations,
+        contact_tolerance: args.contact_tolerance,
+        integrator: args.integrator,
+        event_driven: args.event_driven,
+        with_spin: args.with_spin,
     };
 
+    if args.print_config {
+        write_config(&mut io::stderr(), &input).unwrap();
+    }
+
     let writer = if let Some(output) = args.output {
         Box::new(File::create(output).unwrap()) as Box<dyn Write>
     } else {
         Box::new(stdout())
     };
+    let writer = if let Some(buffer_size) = args.buffer_size {
+        Box::new(BufWriter::with_capacity(buffer_size, writer))
+    } else {
+        writer
+    };
+
+    stop_on_interrupt();
+    drive(
+        &mut PoolSimulation::new(input, observers),
+        writer,
+        |state, t| {
+            args.max_duration
+                .is_some_and(|max_duration| t > max_duration)
+                || args
+                    .min_ball_amount
+                    .is_some_and(|min_ball_amount| state.len() < min_ball_amount)
+                || args.stop_when_still.is_some_and(|epsilon| {
+                    state
+                        .values()
+                        .all(|(ball, _)| ball.velocity.magnitude() < epsilon)
+                })
+        },
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pool::{
+        fixtures::{self, INTO_THE_CORNER, OVERLAPPING_PAIR, TWO_BALL_COLLISION},
+        parser::scaled_output_parser,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{cell::RefCell, io::BufRead, rc::Rc};
+
+    /// Writer whose contents can still be read after handing it to an observer.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        /// Values of every CSV line written so far.
+        fn rows(&self) -> Vec<Vec<Float>> {
+            String::from_utf8(self.0.borrow().clone())
+                .unwrap()
+                .lines()
+                .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+                .collect()
+        }
+    }
+
+    fn config(input: &str) -> InputData {
+        InputData {
+            simple_input_data: fixtures::parse(input),
+            output_condition: OutputCondition::Every(EveryArgs {
+                steps: 1,
+                last: false,
+            }),
+            delta_time_n: 4,
+            with_holes: false,
+            side_hole_radius: None,
+            output_format: OutputFormat::Text,
+            max_displacement: None,
+            checkpoint_every: None,
+            checkpoint_file: PathBuf::new(),
+            restore: None,
+            time_scale: 1.0,
+            contact_iterations: 0,
+            contact_tolerance: 0.0,
+            integrator: Integrator::Gear,
+            event_driven: false,
+            with_spin: false,
+        }
+    }
+
+    fn run(simulation: &mut PoolSimulation, steps: u64) {
+        for _ in 0..steps {
+            simulation.step();
+        }
+    }
+
+    #[test]
+    fn restored_checkpoint_continues_identically() {
+        const STEPS: u64 = 3000;
+
+        let mut straight = PoolSimulation::new(config(TWO_BALL_COLLISION), vec![]);
+        run(&mut straight, 2 * STEPS);
+
+        let mut interrupted = PoolSimulation::new(config(TWO_BALL_COLLISION), vec![]);
+        run(&mut interrupted, STEPS);
+        let mut bytes = vec![];
+        write_checkpoint(
+            &mut bytes,
+            interrupted.iteration,
+            interrupted.time,
+            &interrupted.state,
+        )
+        .unwrap();
+        let mut restored_config = config(TWO_BALL_COLLISION);
+        restored_config.restore = Some(Checkpoint::read_from(&mut bytes.as_slice()).unwrap());
+        let mut resumed = PoolSimulation::new(restored_config, vec![]);
+        run(&mut resumed, STEPS);
+
+        assert_eq!(resumed.iteration, straight.iteration);
+        assert_eq!(resumed.time, straight.time);
+        assert_eq!(resumed.state, straight.state);
+    }
 
-    run(input, writer, |state, t| {
-        args.max_duration
-            .is_some_and(|max_duration| t > max_duration)
-            || args
-                .min_ball_amount
-                .is_some_and(|min_ball_amount| state.len() < min_ball_amount)
-    });
+    #[test]
+    fn unknown_checkpoint_versions_are_rejected() {
+        let mut bytes = vec![];
+        write_checkpoint(&mut bytes, 0, 0.0, &State::new()).unwrap();
+        bytes[CHECKPOINT_MAGIC.len()..][..4]
+            .copy_from_slice(&(CHECKPOINT_VERSION + 1).to_le_bytes());
+
+        let error = Checkpoint::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn checkpoints_without_header_are_rejected() {
+        let mut bytes = vec![];
+        write_checkpoint(&mut bytes, 0, 0.0, &State::new()).unwrap();
+
+        let error = Checkpoint::read_from(&mut &bytes[CHECKPOINT_MAGIC.len() + 4..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn logged_forces_match_the_overlap() {
+        let log = SharedBuffer::default();
+        let force_logger = ForceLogger {
+            writer: log.clone(),
+            last_forces: HashMap::new(),
+            time_scale: 1.0,
+        };
+        let mut simulation =
+            PoolSimulation::new(config(OVERLAPPING_PAIR), vec![Box::new(force_logger)]);
+
+        simulation.emit(&mut io::sink()).unwrap();
+        let (b0, b1) = (simulation.state[&0].0, simulation.state[&1].0);
+        let radius = simulation.config.simple_input_data.ball_radius;
+        let overlap = 2.0 * radius - (b1.position - b0.position).magnitude();
+        assert!(overlap > 0.0);
+        let expected = K * overlap;
+        let ball_count = simulation.state.len();
+        let initial = log.rows();
+        assert_eq!(initial.len(), ball_count);
+        assert_eq!(initial[0][..2], [0.0, 0.0]);
+        assert!((initial[0][2] + expected).abs() < expected * 1e-9);
+        assert_eq!(initial[0][3], 0.0);
+        assert_eq!(initial[1][..2], [0.0, 1.0]);
+        assert!((initial[1][2] - expected).abs() < expected * 1e-9);
+
+        // The frame written after a step has the forces of that same step.
+        simulation.step();
+        simulation.emit(&mut io::sink()).unwrap();
+        let after_step = log.rows().split_off(ball_count);
+        assert_eq!(after_step.len(), ball_count);
+        for (row, (id, force)) in after_step.iter().zip(
+            simulation
+                .buffers
+                .forces
+                .iter()
+                .sorted_by_key(|(id, _)| **id),
+        ) {
+            assert_eq!(row[..], [simulation.time, *id as Float, force.x, force.y]);
+        }
+    }
+
+    #[test]
+    fn substeps_grow_with_speed() {
+        let substeps = [10.0, 100.0, 1000.0, 10000.0].map(|speed| {
+            let ball = Ball {
+                id: 0,
+                position: Vector2::new(112.0, 56.0),
+                velocity: Vector2::new(speed, 0.0),
+                radius: 5.7,
+                angular_velocity: 0.0,
+            };
+            let state = State::from([(0, (ball, [Vector2::zeros(); 4]))]);
+            substeps_needed(&state, 1e-2, 0.1)
+        });
+
+        assert_eq!(substeps[0], 1);
+        assert!(substeps.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn scaled_times_read_back_in_seconds() {
+        const TIME_SCALE: Float = 1000.0;
+        let mut config = config(TWO_BALL_COLLISION);
+        config.time_scale = TIME_SCALE;
+        let energy_log = SharedBuffer::default();
+        let energy_logger = EnergyLogger {
+            writer: energy_log.clone(),
+            ball_mass: config.simple_input_data.ball_mass,
+            time_scale: TIME_SCALE,
+        };
+        let mut simulation = PoolSimulation::new(config, vec![Box::new(energy_logger)]);
+
+        let mut output = vec![];
+        let mut times = vec![];
+        for _ in 0..4 {
+            simulation.emit(&mut output).unwrap();
+            times.push(simulation.time);
+            run(&mut simulation, 100);
+        }
+
+        let read_times = scaled_output_parser(output.as_slice().lines(), TIME_SCALE)
+            .map(|frame| frame.time)
+            .collect_vec();
+        let logged_times = energy_log
+            .rows()
+            .iter()
+            .map(|row| row[0] / TIME_SCALE)
+            .collect_vec();
+        for read in [read_times, logged_times] {
+            assert_eq!(read.len(), times.len());
+            for (read, time) in read.iter().zip(&times) {
+                assert!((read - time).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn metrics_count_the_steps_made() {
+        let log = SharedBuffer::default();
+        let metrics_logger = MetricsLogger {
+            writer: log.clone(),
+            time_scale: 1.0,
+            steps: 0,
+            start: Instant::now(),
+        };
+        let mut config = config(TWO_BALL_COLLISION);
+        config.output_condition = OutputCondition::Every(EveryArgs {
+            steps: 10,
+            last: false,
+        });
+        let mut simulation = PoolSimulation::new(config, vec![Box::new(metrics_logger)]);
+
+        simulation.emit(&mut io::sink()).unwrap();
+        for _ in 0..30 {
+            simulation.step();
+            simulation.emit(&mut io::sink()).unwrap();
+        }
+
+        let rows = log.rows();
+        let steps = rows.iter().map(|row| row[1]).collect_vec();
+        assert_eq!(steps, [0.0, 10.0, 20.0, 30.0]);
+        assert!(rows.iter().all(|row| row[3] == 2.0 && row[4] == 100.0));
+    }
+
+    #[test]
+    fn first_step_starts_from_the_initial_forces() {
+        for integrator in [Integrator::VelocityVerlet, Integrator::Beeman] {
+            let mut config = config(OVERLAPPING_PAIR);
+            config.integrator = integrator;
+            config.delta_time_n = 6;
+            let mut simulation = PoolSimulation::new(config, vec![]);
+            let (b0, b1) = (simulation.state[&0].0, simulation.state[&1].0);
+            let SimpleInputData {
+                ball_radius,
+                ball_mass,
+                ..
+            } = simulation.config.simple_input_data;
+            let overlap = 2.0 * ball_radius - (b1.position - b0.position).magnitude();
+            let acceleration = K * overlap / ball_mass;
+            assert!((simulation.state[&1].1[0].x - acceleration).abs() < acceleration * 1e-9);
+
+            simulation.step();
+            let expected = acceleration * simulation.delta_time;
+            let velocity = simulation.state[&1].0.velocity.x;
+            assert!(
+                (velocity - expected).abs() < expected * 1e-3,
+                "{integrator:?}: {velocity} instead of {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn pocket_events_write_one_line_per_pocketed_ball() {
+        let mut config = config(INTO_THE_CORNER);
+        config.with_holes = true;
+        config.output_condition = OutputCondition::PocketEvents;
+        let mut simulation = PoolSimulation::new(config, vec![]);
+
+        let mut output = vec![];
+        drive(&mut simulation, &mut output, |_, time| time > 1.0).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect_vec();
+        assert_eq!(lines.len(), 1);
+        let fields = lines[0].split(' ').collect_vec();
+        assert!(fields[0].parse::<Float>().unwrap() > 0.0);
+        assert_eq!(fields[1..], ["0", "0", "corner"]);
+    }
+
+    #[test]
+    fn empty_table_finishes_right_away() {
+        let mut simulation = PoolSimulation::new(config("224\n112\n11.4\n5.7\n165\n0\n"), vec![]);
+        assert!(simulation.is_finished());
+
+        let mut output = vec![];
+        drive(&mut simulation, &mut output, |_, _| false).unwrap();
+        assert_eq!(simulation.iteration, 0);
+        let frames = scaled_output_parser(output.as_slice().lines(), 1.0).collect_vec();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].particles.is_empty());
+    }
+
+    #[test]
+    fn buffered_output_matches_the_unbuffered_one() {
+        let output = |buffer_size: Option<usize>| {
+            let mut simulation = PoolSimulation::new(config(TWO_BALL_COLLISION), vec![]);
+            let mut output = vec![];
+            let stop = |_: &State, time: Float| time > 0.05;
+            match buffer_size {
+                Some(capacity) => drive(
+                    &mut simulation,
+                    BufWriter::with_capacity(capacity, &mut output),
+                    stop,
+                ),
+                None => drive(&mut simulation, &mut output, stop),
+            }
+            .unwrap();
+            output
+        };
+
+        let unbuffered = output(None);
+        assert!(!unbuffered.is_empty());
+        for buffer_size in [1, 64, 1 << 16] {
+            assert_eq!(output(Some(buffer_size)), unbuffered);
+        }
+    }
+
+    #[test]
+    fn input_from_a_frame_continues_like_the_run() {
+        let mut running = PoolSimulation::new(config(TWO_BALL_COLLISION), vec![]);
+        let mut output = vec![];
+        running.emit(&mut output).unwrap();
+        for _ in 0..100 {
+            running.step();
+            running.emit(&mut output).unwrap();
+        }
+
+        let last_frame = scaled_output_parser(output.as_slice().lines(), 1.0)
+            .last()
+            .unwrap();
+        let table = fixtures::parse(TWO_BALL_COLLISION);
+        let mut restarted = PoolSimulation::new(
+            InputData {
+                simple_input_data: SimpleInputData::from_frame(last_frame, &table),
+                ..config(TWO_BALL_COLLISION)
+            },
+            vec![],
+        );
+
+        running.step();
+        restarted.step();
+        for ((_, (ball, _)), (_, (restarted_ball, _))) in running.state.iter().zip(&restarted.state)
+        {
+            assert_eq!(ball.id, restarted_ball.id);
+            assert!((ball.position - restarted_ball.position).magnitude() < 1e-9);
+            assert!((ball.velocity - restarted_ball.velocity).magnitude() < 1e-9);
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Calls {
+        steps: usize,
+        frames: usize,
+        forces: usize,
+    }
+
+    /// Observer that only counts how many times each of its methods is called.
+    struct CountingObserver(Rc<RefCell<Calls>>);
+
+    impl SimulationObserver for CountingObserver {
+        fn on_step(&mut self, _state: &State, _time: Float) {
+            self.0.borrow_mut().steps += 1;
+        }
+
+        fn on_frame(&mut self, _state: &State, _time: Float) {
+            self.0.borrow_mut().frames += 1;
+        }
+
+        fn on_forces(&mut self, _forces: &HashMap<ID, Vector2<Float>>, _time: Float) {
+            self.0.borrow_mut().forces += 1;
+        }
+    }
+
+    #[test]
+    fn observers_are_called_once_per_step_and_frame() {
+        let calls = Rc::new(RefCell::new(Calls::default()));
+        let mut config = config(TWO_BALL_COLLISION);
+        config.output_condition = OutputCondition::Every(EveryArgs {
+            steps: 10,
+            last: false,
+        });
+        let mut simulation =
+            PoolSimulation::new(config, vec![Box::new(CountingObserver(calls.clone()))]);
+
+        simulation.emit(&mut io::sink()).unwrap();
+        for _ in 0..30 {
+            simulation.step();
+            simulation.emit(&mut io::sink()).unwrap();
+        }
+
+        // The forces are also logged once before the first step.
+        assert_eq!(
+            *calls.borrow(),
+            Calls {
+                steps: 30,
+                frames: 4,
+                forces: 31,
+            }
+        );
+    }
+
+    #[test]
+    fn written_config_reflects_the_overrides() {
+        let config = InputData {
+            delta_time_n: 5,
+            with_holes: true,
+            side_hole_radius: Some(7.5),
+            integrator: Integrator::Beeman,
+            ..config(TWO_BALL_COLLISION)
+        };
+
+        let mut written = vec![];
+        write_config(&mut written, &config).unwrap();
+
+        let written = String::from_utf8(written).unwrap();
+        for line in [
+            "side hole radius: 7.5",
+            "integrator: Beeman",
+            "delta time: 1e-5 ",
+        ] {
+            assert!(
+                written.lines().any(|l| l.starts_with(line)),
+                "missing {line:?} in:\n{written}"
+            );
+        }
+    }
+
+    #[test]
+    fn racked_balls_settle_against_the_cushion() {
+        // Three balls in a row overlapping each other and the left cushion.
+        let mut config = config(
+            "224
+112
+11.4
+5.7
+165
+3
+0 2.0 56.0 0 0
+1 6.5 56.0 0 0
+2 11.0 56.0 0 0
+",
+        );
+        config.contact_iterations = 100;
+        config.contact_tolerance = 1e-4;
+        let mut simulation = PoolSimulation::new(config, vec![]);
+        let max_overlap = |state: &State| {
+            state
+                .values()
+                .map(|(ball, _)| ball)
+                .tuple_combinations()
+                .map(|(b1, b2)| b1.radius + b2.radius - (b2.position - b1.position).magnitude())
+                .fold(Float::NEG_INFINITY, Float::max)
+        };
+        let into_the_cushion = |state: &State| {
+            let (ball, _) = state[&0];
+            ball.radius - ball.position.x
+        };
+        assert!(max_overlap(&simulation.state) > 1.0);
+        assert!(into_the_cushion(&simulation.state) > 0.0);
+
+        relax_contacts(&mut simulation.state, &simulation.config);
+        let settled = simulation.state.clone();
+        let overlap = max_overlap(&settled);
+        assert!(overlap <= 1e-3, "overlap of {overlap}");
+        assert!(into_the_cushion(&settled) <= 0.0);
+
+        // Once settled they stay still.
+        relax_contacts(&mut simulation.state, &simulation.config);
+        for ((ball, _), (settled_ball, _)) in simulation.state.values().zip(settled.values()) {
+            assert!((ball.position - settled_ball.position).magnitude() <= 1e-3);
+        }
+    }
+
+    #[test]
+    fn low_restitution_bounces_leave_the_cushion() {
+        let mut config = config(
+            "224
+112
+11.4
+5.7
+165
+1
+0 30.0 56.0 -100 0
+restitution 0.1
+",
+        );
+        config.delta_time_n = 5;
+        let mut simulation = PoolSimulation::new(config, vec![]);
+        // The ball reaches the cushion at about 0.27 and bounces back at a tenth of its speed.
+        run(&mut simulation, 40_000);
+
+        let (ball, _) = simulation.state[&0];
+        assert!((ball.velocity.x - 10.0).abs() < 0.1, "{}", ball.velocity.x);
+        assert!(ball.position.x > ball.radius);
+    }
+
+    #[test]
+    fn grid_forces_match_the_brute_force_ones() {
+        let config = config(TWO_BALL_COLLISION);
+        let SimpleInputData {
+            table_width,
+            table_height,
+            ball_radius: radius,
+            ..
+        } = config.simple_input_data;
+        // Random balls inside the table, so only the balls push each other.
+        let mut rng = StdRng::seed_from_u64(0);
+        let balls = (0..200)
+            .map(|id| Ball {
+                id,
+                position: Vector2::new(
+                    rng.gen_range(radius..table_width - radius),
+                    rng.gen_range(radius..table_height - radius),
+                ),
+                velocity: Vector2::zeros(),
+                radius,
+                angular_velocity: 0.0,
+            })
+            .collect_vec();
+        assert!(balls.len() >= CIM_MIN_BALLS);
+        let zeros = || -> HashMap<ID, Vector2<Float>> {
+            balls.iter().map(|b| (b.id, Vector2::zeros())).collect()
+        };
+
+        let mut forces = zeros();
+        calculate_forces(&balls, &config, &mut forces, &mut HashMap::new());
+
+        let mut expected = zeros();
+        for (b1, b2) in balls.iter().tuple_combinations() {
+            if (b1.position - b2.position).magnitude() <= 2.0 * radius {
+                let force = calculate_force(b1, b2, 2.0 * radius);
+                *expected.get_mut(&b1.id).unwrap() += force;
+                *expected.get_mut(&b2.id).unwrap() -= force;
+            }
+        }
+        assert!(expected.values().any(|force| force.magnitude() > 0.0));
+        for ball in &balls {
+            assert!((forces[&ball.id] - expected[&ball.id]).magnitude() < K * 1e-9);
+        }
+    }
+
+    #[test]
+    fn balls_jumping_over_each_other_are_detected() {
+        let checker = TunnelingChecker {
+            ball_radius: 5.7,
+            table_width: 224.0,
+            table_height: 112.0,
+            last_positions: BTreeMap::new(),
+        };
+
+        // From one side to the other in a single step.
+        assert!(checker.passed_through(Vector2::new(-20.0, 0.0), Vector2::new(20.0, 0.0)));
+        // Going past each other without touching.
+        assert!(!checker.passed_through(Vector2::new(-20.0, 12.0), Vector2::new(20.0, 12.0)));
+        // Ending in contact, so the contact force handles it.
+        assert!(!checker.passed_through(Vector2::new(-20.0, 0.0), Vector2::new(-10.0, 0.0)));
+    }
+
+    #[test]
+    fn overlapping_balls_push_each_other_apart() {
+        let mut config = config(OVERLAPPING_PAIR);
+        config.delta_time_n = 6;
+        let mut simulation = PoolSimulation::new(config, vec![]);
+        run(&mut simulation, 5_000);
+
+        let (b0, b1) = (simulation.state[&0].0, simulation.state[&1].0);
+        assert!((b1.position - b0.position).magnitude() > b0.radius + b1.radius);
+        assert!(b0.velocity.x < 0.0 && b1.velocity.x > 0.0);
+
+        // Balls on top of each other are still pushed apart instead of getting NaN forces.
+        let force = calculate_force(&b0, &b0, b0.radius * 2.0);
+        assert!(force.iter().all(|f| f.is_finite()));
+        assert!(force.magnitude() > 0.0);
+    }
+
+    #[test]
+    fn off_center_collisions_make_the_balls_spin() {
+        let mut config = config(
+            "224
+112
+11.4
+5.7
+165
+2
+0 56.0 56.0 100 0
+1 90.0 59.0 0 0
+",
+        );
+        config.delta_time_n = 5;
+        config.with_spin = true;
+        let mut simulation = PoolSimulation::new(config, vec![]);
+        // They collide at about 0.29.
+        run(&mut simulation, 40_000);
+
+        let (b0, b1) = (simulation.state[&0].0, simulation.state[&1].0);
+        assert!(b1.velocity.magnitude() > 0.0);
+        assert!(b0.angular_velocity != 0.0 && b1.angular_velocity != 0.0);
+    }
+
+    #[test]
+    fn friction_slows_a_ball_down_until_it_stops() {
+        let mut simulation = PoolSimulation::new(
+            config(
+                "224
+112
+11.4
+5.7
+165
+1
+0 112.0 56.0 50 25
+friction 100
+",
+            ),
+            vec![],
+        );
+
+        // It stops after about 0.56 seconds.
+        let mut speeds = vec![];
+        for _ in 0..7_000 {
+            simulation.step();
+            speeds.push(simulation.state[&0].0.velocity.magnitude());
+        }
+        assert!(speeds.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(speeds.last(), Some(&0.0));
+    }
 }
@@ -0,0 +1,69 @@
+use std::{env, path::PathBuf, process::Command, time::Instant};
+
+use clap::Parser as _parser;
+
+const INTEGRATORS: [&str; 3] = ["gear", "velocity-verlet", "beeman"];
+
+/// Runs the simulation built next to this binary on the same input with every integrator and
+/// output buffer size, and prints how long each run took.
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    input: PathBuf,
+
+    #[arg(short, long, default_value_t = 5)]
+    delta_time_n: u16,
+
+    #[arg(short, long, default_value_t = 1.0)]
+    max_duration: f64,
+
+    /// Write a frame every this many steps.
+    #[arg(long, default_value_t = 100)]
+    every: u64,
+
+    /// Output buffer sizes to try, 0 to write without a buffer.
+    #[arg(long, num_args = 1.., default_values_t = [0, 8 * 1024, 1024 * 1024])]
+    buffer_sizes: Vec<usize>,
+
+    /// Where the simulations write their output, overwritten by every run. Defaults to a file in
+    /// the temporary directory.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let simulation = env::current_exe()
+        .unwrap()
+        .with_file_name(format!("simulation{}", env::consts::EXE_SUFFIX));
+    let output = args
+        .output
+        .unwrap_or_else(|| env::temp_dir().join("pool-bench-output.txt"));
+
+    println!("integrator,buffer_size,time");
+    for integrator in INTEGRATORS {
+        for &buffer_size in &args.buffer_sizes {
+            let mut command = Command::new(&simulation);
+            command
+                .arg("--input")
+                .arg(&args.input)
+                .arg("--output")
+                .arg(&output)
+                .arg(format!("--delta-time-n={}", args.delta_time_n))
+                .arg(format!("--max-duration={}", args.max_duration))
+                .arg(format!("--integrator={integrator}"));
+            if buffer_size > 0 {
+                command.arg(format!("--buffer-size={buffer_size}"));
+            }
+            command.args(["every", &args.every.to_string(), "--last"]);
+
+            let start = Instant::now();
+            let status = command.status().expect("Error running the simulation.");
+            let delta = (Instant::now() - start).as_secs_f64();
+            assert!(status.success(), "The simulation failed with {integrator}.");
+
+            println!("{integrator},{buffer_size},{delta}");
+        }
+    }
+}
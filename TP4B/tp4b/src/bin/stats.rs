@@ -0,0 +1,121 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use cim::particles::ID;
+use clap::Parser as _parser;
+use nalgebra::Vector2;
+use pool::{models::Frame, parser::scaled_output_parser, Float};
+
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Factor the simulation multiplied the output times by with `--time-scale`.
+    #[arg(long, default_value_t = 1.0)]
+    time_scale: Float,
+}
+
+fn main() {
+    let args = Args::parse();
+    let output_file = File::open(args.output).unwrap();
+    let stats = Stats::from_frames(scaled_output_parser(
+        BufReader::new(output_file).lines(),
+        args.time_scale,
+    ));
+
+    println!("frames: {}", stats.frame_count);
+    println!("duration: {}", stats.duration);
+    println!("max speed: {}", stats.max_speed);
+    println!("ball count:");
+    for (time, count) in stats.ball_counts {
+        println!("  {time}: {count}");
+    }
+    println!("distance traveled:");
+    for (id, distance) in stats.distances {
+        println!("  {id}: {distance}");
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    frame_count: usize,
+    duration: Float,
+    max_speed: Float,
+    /// Time and amount of balls of the frames where the amount of balls changed.
+    ball_counts: Vec<(Float, usize)>,
+    distances: BTreeMap<ID, Float>,
+}
+
+impl Stats {
+    fn from_frames(frames: impl Iterator<Item = Frame>) -> Self {
+        let mut stats = Self::default();
+        let mut first_time = None;
+        let mut last_positions: BTreeMap<ID, Vector2<Float>> = BTreeMap::new();
+
+        for frame in frames {
+            let Frame {
+                time,
+                particles: balls,
+            } = frame;
+            stats.frame_count += 1;
+            stats.duration = time - *first_time.get_or_insert(time);
+
+            if stats
+                .ball_counts
+                .last()
+                .map_or(true, |&(_, count)| count != balls.len())
+            {
+                stats.ball_counts.push((time, balls.len()));
+            }
+
+            let mut positions = BTreeMap::new();
+            for ball in balls {
+                stats.max_speed = stats.max_speed.max(ball.velocity.magnitude());
+                let distance = stats.distances.entry(ball.id).or_default();
+                if let Some(last_position) = last_positions.get(&ball.id) {
+                    *distance += (ball.position - last_position).magnitude();
+                }
+                positions.insert(ball.id, ball.position);
+            }
+            last_positions = positions;
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pool::parser::output_parser;
+
+    /// Ball 1 is pocketed after the second frame.
+    const OUTPUT: &str = "2
+0
+0 10 10 3 4
+1 50 50 0 0
+2
+0.5
+0 13 14 3 4
+1 50 50 0 1
+1
+1.5
+0 16 18 6 8
+";
+
+    #[test]
+    fn synthetic_output_stats() {
+        let stats = Stats::from_frames(output_parser(OUTPUT.as_bytes().lines()));
+
+        assert_eq!(stats.frame_count, 3);
+        assert_eq!(stats.duration, 1.5);
+        assert_eq!(stats.max_speed, 10.0);
+        assert_eq!(stats.ball_counts, [(0.0, 2), (1.5, 1)]);
+        assert_eq!(stats.distances, BTreeMap::from([(0, 10.0), (1, 0.0)]));
+    }
+}
@@ -3,13 +3,12 @@
 use capturable_visualization::VisualizationBuilder;
 use chumsky::Parser;
 use clap::Parser as _parser;
-use nalgebra::Vector2;
 use nannou::prelude::*;
 use pool::{
     draw::draw as draw_pool,
     models::{Frame, InputData},
     parser::{input_parser, output_parser},
-    Float, HOLE_POSITIONS,
+    table_holes, Float, Hole,
 };
 use std::{
     fs::{read_to_string, File},
@@ -31,6 +30,9 @@ struct Args {
 
     #[arg(short, long)]
     no_holes: bool,
+
+    #[arg(long)]
+    side_hole_radius: Option<Float>,
 }
 
 fn main() {
@@ -52,7 +54,7 @@ struct Model {
     system_info: InputData,
     frame_iter: Box<dyn Iterator<Item = Frame>>,
     frame: Frame,
-    holes: Vec<Vector2<Float>>,
+    holes: Vec<Hole>,
 }
 
 fn model(_app: &App, args: Args) -> Model {
@@ -68,18 +70,19 @@ fn model(_app: &App, args: Args) -> Model {
     let holes = if args.no_holes {
         Vec::with_capacity(0)
     } else {
-        Vec::from(HOLE_POSITIONS.map(|v| {
-            v.component_mul(&Vector2::new(
-                system_info.table_width,
-                system_info.table_height,
-            ))
-        }))
+        table_holes(
+            system_info.table_width,
+            system_info.table_height,
+            &system_info.hole_positions,
+            system_info.hole_radius,
+            args.side_hole_radius.unwrap_or(system_info.hole_radius),
+        )
     };
 
     Model {
         frame: Frame {
             time: 0.0,
-            balls: system_info.balls.clone(),
+            particles: system_info.balls.clone(),
         },
         frame_iter,
         holes,
@@ -96,7 +99,7 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
 fn draw(_app: &App, model: &Model, draw: &Draw) {
     draw_pool(
         &model.system_info,
-        model.frame.balls.iter().cloned(),
+        model.frame.particles.iter().cloned(),
         &model.holes,
         draw,
     );
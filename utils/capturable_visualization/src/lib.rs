@@ -1,6 +1,9 @@
 #![feature(let_chains)]
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use frame_capturer::{CaptureMode, FrameCapturer};
 use nannou::{prelude::*, wgpu::ToTextureView};
@@ -16,10 +19,13 @@ struct CaptureState {
     frame_capturer: FrameCapturer,
     texture_copy: wgpu::Texture,
     texture_copy_view: wgpu::TextureView,
+    fps: Option<f64>,
+    max_frames: Option<usize>,
 }
 
 struct Model<M> {
     aspect_ratio: f32,
+    screenshot_directory: PathBuf,
     capture_state: Option<CaptureState>,
     user_model: M,
     events: VisualizationEvents<M>,
@@ -27,11 +33,22 @@ struct Model<M> {
 
 struct CaptureData {
     directory: PathBuf,
-    resolution: (u32, u32),
+    /// 1080 pixels high with the aspect ratio of the visualization if not given.
+    resolution: Option<(u32, u32)>,
+    fps: Option<f64>,
 }
 
+/// Pressing F12 on any visualization saves the next window frame to
+/// `<screenshot directory>/screenshot-<unix time in ms>.png`, the directory being the working
+/// directory unless changed with `with_screenshot_directory`.
+///
+/// When capturing, every frame is saved to the capture directory as `<frame number>.png`. The
+/// sequence can be turned into a video with
+/// `ffmpeg -framerate <fps> -pattern_type glob -i '<directory>/*.png' video.mp4`.
 pub struct VisualizationBuilder<M = ()> {
     capture_data: Option<CaptureData>,
+    max_captured_frames: Option<usize>,
+    screenshot_directory: PathBuf,
     aspect_ratio: f32,
     events: VisualizationEvents<M>,
     model: Box<ModelFn<M>>,
@@ -56,6 +73,8 @@ impl<M: 'static> VisualizationBuilder<M> {
                 event: None,
             },
             capture_data: None,
+            max_captured_frames: None,
+            screenshot_directory: PathBuf::from("."),
             aspect_ratio: 1.0,
         }
     }
@@ -63,10 +82,14 @@ impl<M: 'static> VisualizationBuilder<M> {
     fn create_model(self, app: &App) -> Model<M> {
         Model {
             aspect_ratio: self.aspect_ratio,
+            screenshot_directory: self.screenshot_directory,
             user_model: (self.model)(app),
             events: self.events,
             capture_state: self.capture_data.map(|capture_data| {
-                let texture_size = [capture_data.resolution.0, capture_data.resolution.1];
+                let (width, height) = capture_data
+                    .resolution
+                    .unwrap_or(((1080.0 * self.aspect_ratio).round() as u32, 1080));
+                let texture_size = [width, height];
 
                 let space_to_texture = Mat4::from_translation(
                     (-Vec2::from(texture_size.map(|v| v as f32)) / 2.0).extend(0.0),
@@ -98,6 +121,8 @@ impl<M: 'static> VisualizationBuilder<M> {
                     ),
                     texture_copy,
                     texture_copy_view,
+                    fps: capture_data.fps,
+                    max_frames: self.max_captured_frames,
                 }
             }),
         }
@@ -132,11 +157,35 @@ impl<M: 'static> VisualizationBuilder<M> {
         let (width, height) = resolution;
         self.capture_data = Some(CaptureData {
             directory,
-            resolution: (width, height),
+            resolution: Some((width, height)),
+            fps: None,
         });
         self
     }
 
+    /// Captures every frame to `directory` as if the visualization ran at exactly `fps` frames
+    /// per second: updates are told `1 / fps` seconds passed no matter how long the frames took
+    /// to render, so the video plays at the same pace as the visualization would.
+    pub fn capture(mut self, directory: PathBuf, fps: f64) -> Self {
+        self.capture_data = Some(CaptureData {
+            directory,
+            resolution: self.capture_data.and_then(|data| data.resolution),
+            fps: Some(fps),
+        });
+        self
+    }
+
+    /// Closes the visualization once this many frames have been captured.
+    pub fn with_capture_limit(mut self, frames: usize) -> Self {
+        self.max_captured_frames = Some(frames);
+        self
+    }
+
+    pub fn with_screenshot_directory(mut self, directory: PathBuf) -> Self {
+        self.screenshot_directory = directory;
+        self
+    }
+
     pub fn run(self) {
         nannou::app(|app| {
             app.new_window().view(view::<M>).event(event::<M>).build().unwrap();
@@ -148,13 +197,40 @@ impl<M: 'static> VisualizationBuilder<M> {
     }
 }
 
+fn screenshot_path(directory: &Path, time: SystemTime) -> PathBuf {
+    let timestamp = time.duration_since(UNIX_EPOCH).unwrap().as_millis();
+    directory
+        .join(format!("screenshot-{timestamp}"))
+        .with_extension("png")
+}
+
 fn event<M>(app: &App, model: &mut Model<M>, window_event: WindowEvent) {
+    if let WindowEvent::KeyPressed(Key::F12) = window_event {
+        app.main_window().capture_frame(screenshot_path(
+            &model.screenshot_directory,
+            SystemTime::now(),
+        ));
+    }
     if let Some(event) = model.events.event {
         event(app, &mut model.user_model, window_event);
     }
 }
 
-fn update<M>(app: &App, model: &mut Model<M>, update_data: Update) {
+fn update<M>(app: &App, model: &mut Model<M>, mut update_data: Update) {
+    if let Some(capture_state) = &model.capture_state {
+        let frame_count = capture_state.frame_capturer.frame_count();
+        if let Some(max_frames) = capture_state.max_frames && frame_count >= max_frames {
+            app.quit();
+            return;
+        }
+        if let Some(fps) = capture_state.fps {
+            let frame_time = Duration::from_secs_f64(1.0 / fps);
+            update_data = Update {
+                since_last: frame_time,
+                since_start: frame_time * frame_count as u32,
+            };
+        }
+    }
     if let Some(update) = model.events.update {
         update(app, &mut model.user_model, update_data);
     }
@@ -222,3 +298,27 @@ fn exit<M>(app: &App, model: Model<M>) {
         exit(app, model.user_model);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshots_are_named_after_the_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        assert_eq!(
+            screenshot_path(Path::new("captures"), time),
+            Path::new("captures/screenshot-1700000000123.png")
+        );
+    }
+
+    #[test]
+    fn screenshots_go_to_the_chosen_directory() {
+        let builder = VisualizationBuilder::new(|_| ());
+        assert_eq!(builder.screenshot_directory, Path::new("."));
+
+        let builder = builder.with_screenshot_directory(PathBuf::from("captures"));
+        assert_eq!(builder.screenshot_directory, Path::new("captures"));
+    }
+}
@@ -4,46 +4,109 @@ use itertools::Itertools;
 use nalgebra::Vector2;
 
 use crate::{
-    neighbor_finder::{NeighborFinder, NeighborMap},
+    neighbor_finder::{DistanceMap, NeighborFinder, NeighborMap},
     particles::{CircularParticle, ID},
 };
 
 pub struct CimNeighborFinder;
 
 pub struct SystemInfo {
+    /// Whether the space wraps around. When it doesn't, cells on the border have no neighbors
+    /// past the edge, so particles on opposite sides are never linked.
     pub cyclic: bool,
     pub interaction_radius: f64,
     pub space_width: f64,
     pub space_height: f64,
     pub columns: usize,
     pub rows: usize,
+    pub max_neighbors: Option<usize>,
+}
+
+impl SystemInfo {
+    /// Most cells that fit along `length` while still being wide enough for particles of up to
+    /// `max_radius` (see `is_grid_wide_enough`), at least one.
+    pub fn cells_along(length: f64, interaction_radius: f64, max_radius: f64) -> usize {
+        ((length / (interaction_radius + 2.0 * max_radius)).floor() as usize).max(1)
+    }
+
+    /// Whether every pair of particles closer than `interaction_radius` is guaranteed to be in the
+    /// same or adjacent cells, given the radius of the biggest particle.
+    pub fn is_grid_wide_enough(&self, max_radius: f64) -> bool {
+        let min_cell_size = self.interaction_radius + 2.0 * max_radius;
+        self.space_width / self.columns as f64 >= min_cell_size
+            && self.space_height / self.rows as f64 >= min_cell_size
+    }
+}
+
+/// Interaction radius needed so that two particles moving at most at `max_speed` that will touch
+/// within the next `delta_time` are already found as neighbors.
+pub fn approach_margin(max_speed: f64, delta_time: f64) -> f64 {
+    2.0 * max_speed * delta_time
 }
 
 impl<P: CircularParticle> NeighborFinder<P, SystemInfo> for CimNeighborFinder {
     fn find_neighbors(particles: &[P], system: SystemInfo) -> NeighborMap<ID> {
-        let mut cells: BTreeMap<(_, _), Vec<P>> = BTreeMap::new();
-
-        let cell_width = system.space_width / system.columns as f64;
-        let cell_height = system.space_height / system.rows as f64;
-        let get_cell_index = |particle: &P| -> Vector2<usize> {
-            particle
-                .get_position()
-                .component_div(&Vector2::new(cell_width, cell_height))
-                .apply_into(|v| *v = v.floor())
-                .try_cast()
-                .unwrap()
-        };
-        let get_cells_to_check = |cell_index: Vector2<usize>| {
-            [
-                Vector2::new(0i32, 0),
-                Vector2::new(1, 0),
-                Vector2::new(1, 1),
-                Vector2::new(0, 1),
-                Vector2::new(-1, 1),
-            ]
-            .into_iter()
+        let mut map = NeighborMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, _| map.add_pair(p1, p2));
+        map
+    }
+
+    fn find_neighbors_with_distance(particles: &[P], system: SystemInfo) -> DistanceMap<ID> {
+        let mut map = DistanceMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, distance| {
+            map.add_pair(p1, p2, distance)
+        });
+        map
+    }
+}
+
+/// Calls `visit` with the ids and distance of every pair within the interaction radius.
+fn visit_pairs<P: CircularParticle>(
+    particles: &[P],
+    system: &SystemInfo,
+    mut visit: impl FnMut(ID, ID, f64),
+) {
+    // Without pairs there is no need to build the grid.
+    if particles.len() < 2 {
+        return;
+    }
+
+    let mut cells: BTreeMap<(_, _), Vec<P>> = BTreeMap::new();
+
+    let cell_width = system.space_width / system.columns as f64;
+    let cell_height = system.space_height / system.rows as f64;
+    // Particles slightly outside of the space (like balls pressing against a wall) are put in
+    // the border cells.
+    let get_cell_index = |particle: &P| -> Vector2<usize> {
+        let index = particle
+            .get_position()
+            .component_div(&Vector2::new(cell_width, cell_height))
+            .apply_into(|v| *v = v.floor().max(0.0));
+        Vector2::new(
+            (index.x as usize).min(system.columns - 1),
+            (index.y as usize).min(system.rows - 1),
+        )
+    };
+    // Cells can be smaller than the interaction distance, in which case the pairs can be more
+    // than one cell apart.
+    let max_radius = particles.iter().map(|p| p.get_radius()).fold(0.0, f64::max);
+    let reach = system.interaction_radius + 2.0 * max_radius;
+    let extent_x = ((reach / cell_width).ceil() as i32).max(1);
+    let extent_y = ((reach / cell_height).ceil() as i32).max(1);
+    // Half of the neighborhood, so that every pair of cells is only checked once.
+    let offsets = (0..=extent_y)
+        .flat_map(|y| {
+            let min_x = if y == 0 { 0 } else { -extent_x };
+            (min_x..=extent_x).map(move |x| Vector2::new(x, y))
+        })
+        .collect_vec();
+    // On small cyclic grids different offsets can wrap to the same cell, so it is only
+    // checked once.
+    let get_cells_to_check = |cell_index: Vector2<usize>| {
+        offsets
+            .iter()
             .filter_map(move |v| {
-                let new_index = v + cell_index.cast();
+                let new_index = v + cell_index.cast::<i32>();
                 if system.cyclic {
                     Some(Vector2::new(
                         new_index.x.rem_euclid(system.columns as i32) as usize,
@@ -57,41 +120,163 @@ impl<P: CircularParticle> NeighborFinder<P, SystemInfo> for CimNeighborFinder {
                         .then(|| new_index.try_cast().unwrap())
                 }
             })
-        };
+            .unique()
+    };
 
-        // Fill the cell matrix with particles.
-        for particle in particles {
-            let cell_index = get_cell_index(particle);
-            cells
-                .entry((cell_index.y, cell_index.x))
-                .or_insert_with(|| Vec::with_capacity(2))
-                .push(*particle);
-        }
+    // Fill the cell matrix with particles.
+    for particle in particles {
+        let cell_index = get_cell_index(particle);
+        cells
+            .entry((cell_index.y, cell_index.x))
+            .or_insert_with(|| Vec::with_capacity(2))
+            .push(*particle);
+    }
 
-        let mut map = NeighborMap::default();
-
-        for (cell_index, cell) in &cells {
-            let cell_index = Vector2::new(cell_index.1, cell_index.0);
-            for other_cell_index in get_cells_to_check(cell_index) {
-                if let Some(other_cell) = cells.get(&(other_cell_index.y, other_cell_index.x)) {
-                    for (particle, other) in cell.iter().cartesian_product(other_cell.iter()) {
-                        // If we are in the same cell, we only check the same pair once.
-                        if (other_cell_index != cell_index || other.get_id() > particle.get_id())
-                            && particle.is_within_distance_of(
-                                other,
-                                system.interaction_radius,
-                                system.space_width,
-                                system.space_height,
-                                system.cyclic,
-                            )
-                        {
-                            map.add_pair(particle.get_id(), other.get_id());
-                        }
+    for (cell_index, cell) in &cells {
+        let cell_index = Vector2::new(cell_index.1, cell_index.0);
+        for other_cell_index in get_cells_to_check(cell_index) {
+            if let Some(other_cell) = cells.get(&(other_cell_index.y, other_cell_index.x)) {
+                for (particle, other) in cell.iter().cartesian_product(other_cell.iter()) {
+                    // If we are in the same cell, we only check the same pair once.
+                    if other_cell_index == cell_index && other.get_id() <= particle.get_id() {
+                        continue;
+                    }
+                    let distance = particle.distance_to(
+                        other,
+                        system.space_width,
+                        system.space_height,
+                        system.cyclic,
+                    );
+                    if distance <= system.interaction_radius {
+                        visit(particle.get_id(), other.get_id(), distance);
                     }
                 }
             }
         }
+    }
+}
 
-        map
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{cim_system, disk, scattered_disks, Disk};
+
+    #[test]
+    fn empty_and_single_particle_inputs_have_no_pairs() {
+        let empty: [Disk; 0] = [];
+        assert_eq!(
+            CimNeighborFinder::find_neighbors(&empty, cim_system(10.0, 10, 1.0, true)).pair_count(),
+            0
+        );
+
+        let single = [disk(0, 5.0, 5.0, 0.5)];
+        let neighbors = CimNeighborFinder::find_neighbors(&single, cim_system(10.0, 10, 1.0, true));
+        assert_eq!(neighbors.pair_count(), 0);
+        assert_eq!(neighbors.get_neighbors(0).count(), 0);
+    }
+
+    #[test]
+    fn ties_on_a_lattice_are_listed_by_id() {
+        // 3x3 lattice where the center has four neighbors at the same distance.
+        let lattice = (0..9)
+            .map(|id| disk(id, 4.0 + (id % 3) as f64, 4.0 + (id / 3) as f64, 0.1))
+            .collect_vec();
+        let reversed = lattice.iter().rev().copied().collect_vec();
+
+        for particles in [lattice, reversed] {
+            let neighbors =
+                CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, 5, 0.85, false));
+            assert_eq!(
+                neighbors.get_neighbors(4).copied().collect_vec(),
+                [1, 3, 5, 7]
+            );
+        }
+    }
+
+    #[test]
+    fn fast_approaching_pairs_are_found_with_the_margin() {
+        // Moving towards each other at 5 each, the gap of 1 closes within a step of 0.1.
+        let particles = [disk(0, 4.0, 5.0, 0.5), disk(1, 6.0, 5.0, 0.5)];
+        let margin = approach_margin(5.0, 0.1);
+
+        let without_margin =
+            CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, 5, 0.0, false));
+        assert!(!without_margin.has_pair(0, 1));
+
+        let system = cim_system(10.0, 5, margin, false);
+        assert!(system.is_grid_wide_enough(0.5));
+        assert!(CimNeighborFinder::find_neighbors(&particles, system).has_pair(0, 1));
+    }
+
+    #[test]
+    fn cell_size_doesnt_change_the_neighbors() {
+        let particles = scattered_disks(200, 10.0, 0.1);
+
+        for cyclic in [false, true] {
+            let neighbors = |cells| {
+                CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, cells, 0.5, cyclic))
+                    .to_string()
+            };
+            let expected = neighbors(1);
+            assert!(!expected.is_empty());
+            for cells in [2, 3, 7, 14, 40] {
+                assert_eq!(
+                    neighbors(cells),
+                    expected,
+                    "{cells} cells, cyclic: {cyclic}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dense_clusters_over_the_cap_are_truncated() {
+        let cluster = (0..10)
+            .map(|id| disk(id, 5.0 + 0.01 * id as f64, 5.0, 0.1))
+            .collect_vec();
+        let capped = |max_neighbors| {
+            CimNeighborFinder::find_neighbors(
+                &cluster,
+                SystemInfo {
+                    max_neighbors,
+                    ..cim_system(10.0, 5, 0.5, false)
+                },
+            )
+        };
+
+        let neighbors = capped(Some(3));
+        assert!(neighbors.is_truncated());
+        assert!((0..10).all(|id| neighbors.get_neighbors(id).count() <= 3));
+
+        let neighbors = capped(Some(9));
+        assert!(!neighbors.is_truncated());
+        assert_eq!(neighbors.pair_count(), 45);
+    }
+
+    #[test]
+    fn opposite_borders_are_neighbors_only_when_cyclic() {
+        let particles = [disk(0, 0.05, 5.0, 0.0), disk(1, 9.95, 5.0, 0.0)];
+
+        for cyclic in [false, true] {
+            let neighbors =
+                CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, 10, 0.2, cyclic));
+            assert_eq!(neighbors.has_pair(0, 1), cyclic);
+        }
+    }
+
+    #[test]
+    fn big_particles_reach_small_ones_across_cells() {
+        // The centers are 2.4 apart but the borders only 0.3.
+        let particles = [disk(0, 2.0, 5.0, 2.0), disk(1, 4.4, 5.0, 0.1)];
+        let cells = SystemInfo::cells_along(10.0, 0.5, 2.0);
+        let system = cim_system(10.0, cells, 0.5, false);
+        assert!(system.is_grid_wide_enough(2.0));
+
+        let neighbors = CimNeighborFinder::find_neighbors_with_distance(&particles, system);
+        assert!((neighbors.distance(0, 1).unwrap() - 0.3).abs() < 1e-9);
+        // Also with cells as small as the interaction radius.
+        let neighbors =
+            CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, 20, 0.5, false));
+        assert!(neighbors.has_pair(0, 1));
     }
 }
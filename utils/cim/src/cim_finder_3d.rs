@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use itertools::{iproduct, Itertools};
+use nalgebra::Vector3;
+
+use crate::{
+    neighbor_finder::{DistanceMap, NeighborFinder, NeighborMap},
+    particles::{SphericalParticle, ID},
+};
+
+/// Cell index method over a 3D grid of `columns` x `rows` x `layers` cells.
+pub struct CimNeighborFinder3D;
+
+pub struct SystemInfo {
+    pub cyclic: bool,
+    pub interaction_radius: f64,
+    pub space_width: f64,
+    pub space_height: f64,
+    pub space_depth: f64,
+    pub columns: usize,
+    pub rows: usize,
+    pub layers: usize,
+    pub max_neighbors: Option<usize>,
+}
+
+impl SystemInfo {
+    fn space_size(&self) -> Vector3<f64> {
+        Vector3::new(self.space_width, self.space_height, self.space_depth)
+    }
+
+    fn cell_counts(&self) -> Vector3<usize> {
+        Vector3::new(self.columns, self.rows, self.layers)
+    }
+}
+
+impl<P: SphericalParticle> NeighborFinder<P, SystemInfo> for CimNeighborFinder3D {
+    fn find_neighbors(particles: &[P], system: SystemInfo) -> NeighborMap<ID> {
+        let mut map = NeighborMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, _| map.add_pair(p1, p2));
+        map
+    }
+
+    fn find_neighbors_with_distance(particles: &[P], system: SystemInfo) -> DistanceMap<ID> {
+        let mut map = DistanceMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, distance| {
+            map.add_pair(p1, p2, distance)
+        });
+        map
+    }
+}
+
+/// Checks every pair of particles, for comparing against `CimNeighborFinder3D`.
+pub struct SimpleNeighborFinder3D;
+
+impl<P: SphericalParticle> NeighborFinder<P, SystemInfo> for SimpleNeighborFinder3D {
+    fn find_neighbors(particles: &[P], system: SystemInfo) -> NeighborMap<ID> {
+        Self::find_neighbors_with_distance(particles, system).into_neighbors()
+    }
+
+    fn find_neighbors_with_distance(particles: &[P], system: SystemInfo) -> DistanceMap<ID> {
+        let mut map = DistanceMap::with_max_neighbors(system.max_neighbors);
+        for (p1, p2) in particles.iter().tuple_combinations() {
+            let distance = p1.distance_to(p2, system.space_size(), system.cyclic);
+            if distance <= system.interaction_radius {
+                map.add_pair(p1.get_id(), p2.get_id(), distance);
+            }
+        }
+        map
+    }
+}
+
+/// Calls `visit` with the ids and distance of every pair within the interaction radius.
+fn visit_pairs<P: SphericalParticle>(
+    particles: &[P],
+    system: &SystemInfo,
+    mut visit: impl FnMut(ID, ID, f64),
+) {
+    // Without pairs there is no need to build the grid.
+    if particles.len() < 2 {
+        return;
+    }
+
+    let space_size = system.space_size();
+    let cell_counts = system.cell_counts();
+    let cell_size = space_size.component_div(&cell_counts.cast());
+    // Particles slightly outside of the space are put in the border cells.
+    let get_cell_index = |particle: &P| -> Vector3<usize> {
+        particle
+            .get_position()
+            .component_div(&cell_size)
+            .zip_map(&cell_counts, |v, count| {
+                (v.floor().max(0.0) as usize).min(count - 1)
+            })
+    };
+    // Cells can be smaller than the interaction distance, in which case the pairs can be more
+    // than one cell apart.
+    let max_radius = particles.iter().map(|p| p.get_radius()).fold(0.0, f64::max);
+    let reach = system.interaction_radius + 2.0 * max_radius;
+    let extent = cell_size.map(|size| ((reach / size).ceil() as i32).max(1));
+    // Half of the neighborhood (the 13 cells after the current one for a 27 cell neighborhood),
+    // so that every pair of cells is only checked once.
+    let offsets = iproduct!(
+        -extent.z..=extent.z,
+        -extent.y..=extent.y,
+        -extent.x..=extent.x
+    )
+    .map(|(z, y, x)| Vector3::new(x, y, z))
+    .filter(|v| (v.z, v.y, v.x) >= (0, 0, 0))
+    .collect_vec();
+    // On small cyclic grids different offsets can wrap to the same cell, so it is only checked
+    // once.
+    let get_cells_to_check = |cell_index: Vector3<usize>| {
+        offsets
+            .iter()
+            .filter_map(move |v| {
+                let new_index = v + cell_index.cast::<i32>();
+                if system.cyclic {
+                    Some(
+                        new_index
+                            .zip_map(&cell_counts, |i, count| i.rem_euclid(count as i32) as usize),
+                    )
+                } else {
+                    new_index
+                        .iter()
+                        .zip(cell_counts.iter())
+                        .all(|(&i, &count)| i >= 0 && (i as usize) < count)
+                        .then(|| new_index.map(|i| i as usize))
+                }
+            })
+            .unique()
+    };
+
+    // Fill the cell matrix with particles.
+    let mut cells: BTreeMap<(_, _, _), Vec<P>> = BTreeMap::new();
+    for particle in particles {
+        let cell_index = get_cell_index(particle);
+        cells
+            .entry((cell_index.z, cell_index.y, cell_index.x))
+            .or_insert_with(|| Vec::with_capacity(2))
+            .push(*particle);
+    }
+
+    for (&(z, y, x), cell) in &cells {
+        let cell_index = Vector3::new(x, y, z);
+        for other_cell_index in get_cells_to_check(cell_index) {
+            let key = (other_cell_index.z, other_cell_index.y, other_cell_index.x);
+            let Some(other_cell) = cells.get(&key) else {
+                continue;
+            };
+            for (particle, other) in cell.iter().cartesian_product(other_cell.iter()) {
+                // If we are in the same cell, we only check the same pair once.
+                if other_cell_index == cell_index && other.get_id() <= particle.get_id() {
+                    continue;
+                }
+                let distance = particle.distance_to(other, space_size, system.cyclic);
+                if distance <= system.interaction_radius {
+                    visit(particle.get_id(), other.get_id(), distance);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::scattered_spheres;
+
+    fn system(cells: usize, cyclic: bool) -> SystemInfo {
+        SystemInfo {
+            cyclic,
+            interaction_radius: 0.5,
+            space_width: 10.0,
+            space_height: 10.0,
+            space_depth: 10.0,
+            columns: cells,
+            rows: cells,
+            layers: cells,
+            max_neighbors: None,
+        }
+    }
+
+    #[test]
+    fn cim_matches_the_brute_force() {
+        let particles = scattered_spheres(500, 10.0, 0.2);
+
+        for cyclic in [false, true] {
+            let expected =
+                SimpleNeighborFinder3D::find_neighbors(&particles, system(1, cyclic)).to_string();
+            assert!(!expected.is_empty());
+            for cells in [1, 3, 5, 11] {
+                let neighbors =
+                    CimNeighborFinder3D::find_neighbors(&particles, system(cells, cyclic));
+                assert_eq!(
+                    neighbors.to_string(),
+                    expected,
+                    "{cells} cells, cyclic: {cyclic}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use nalgebra::{Vector2, Vector3};
+
+use crate::{
+    cim_finder,
+    particles::{CircularParticle, SphericalParticle, ID},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Disk {
+    pub id: ID,
+    pub position: Vector2<f64>,
+    pub radius: f64,
+}
+
+impl CircularParticle for Disk {
+    fn get_id(&self) -> ID {
+        self.id
+    }
+
+    fn get_position(&self) -> Vector2<f64> {
+        self.position
+    }
+
+    fn get_radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+pub fn disk(id: ID, x: f64, y: f64, radius: f64) -> Disk {
+    Disk {
+        id,
+        position: Vector2::new(x, y),
+        radius,
+    }
+}
+
+/// Square space of side `length` split in `cells` by `cells`.
+pub fn cim_system(
+    length: f64,
+    cells: usize,
+    interaction_radius: f64,
+    cyclic: bool,
+) -> cim_finder::SystemInfo {
+    cim_finder::SystemInfo {
+        cyclic,
+        interaction_radius,
+        space_width: length,
+        space_height: length,
+        columns: cells,
+        rows: cells,
+        max_neighbors: None,
+    }
+}
+
+/// `count` disks evenly spread over a square of side `length` following the R2 sequence, so tests
+/// get an irregular layout without a random number generator.
+pub fn scattered_disks(count: usize, length: f64, radius: f64) -> Vec<Disk> {
+    const A1: f64 = 0.7548776662466927;
+    const A2: f64 = 0.5698402909980532;
+    (0..count)
+        .map(|id| {
+            let x = (0.5 + A1 * id as f64).fract() * length;
+            let y = (0.5 + A2 * id as f64).fract() * length;
+            disk(id, x, y, radius)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    pub id: ID,
+    pub position: Vector3<f64>,
+    pub radius: f64,
+}
+
+impl SphericalParticle for Sphere {
+    fn get_id(&self) -> ID {
+        self.id
+    }
+
+    fn get_position(&self) -> Vector3<f64> {
+        self.position
+    }
+
+    fn get_radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+/// Same as `scattered_disks` in a cube of side `length`.
+pub fn scattered_spheres(count: usize, length: f64, radius: f64) -> Vec<Sphere> {
+    const A: [f64; 3] = [0.8191725133961644, 0.671043606703789, 0.5497004779019701];
+    (0..count)
+        .map(|id| Sphere {
+            id,
+            position: Vector3::from_fn(|i, _| (0.5 + A[i] * id as f64).fract() * length),
+            radius,
+        })
+        .collect()
+}
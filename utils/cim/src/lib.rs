@@ -1,4 +1,7 @@
 pub mod cim_finder;
+pub mod cim_finder_3d;
+#[cfg(test)]
+pub(crate) mod fixtures;
 pub mod neighbor_finder;
 pub mod particles;
 pub mod simple_finder;
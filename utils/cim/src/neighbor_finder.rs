@@ -6,30 +6,76 @@ use std::{
 };
 
 use itertools::Either;
+use ndarray::Array2;
+
+use crate::particles::CircularParticle;
 
 pub trait NeighborFinder<Particle, SystemInfo> {
     fn find_neighbors(particles: &[Particle], system: SystemInfo) -> NeighborMap<usize>;
+
+    /// Same as `find_neighbors`, also keeping the distance between the borders of every pair.
+    fn find_neighbors_with_distance(
+        particles: &[Particle],
+        system: SystemInfo,
+    ) -> DistanceMap<usize>;
 }
 
+/// Neighbors are kept in ordered sets, so both the particles and their neighbor lists are always
+/// iterated in ascending id order regardless of how the pairs were inserted.
 #[derive(Debug, Default)]
 pub struct NeighborMap<ID> {
     map: BTreeMap<ID, BTreeSet<ID>>,
+    max_neighbors: Option<usize>,
+    truncated: bool,
 }
 
 impl<ID: Hash + Ord + Eq + Copy> NeighborMap<ID> {
     pub fn new(map: BTreeMap<ID, BTreeSet<ID>>) -> Self {
-        Self { map }
+        Self {
+            map,
+            max_neighbors: None,
+            truncated: false,
+        }
+    }
+
+    /// Map that stops recording pairs for a particle once it has `max_neighbors` neighbors.
+    pub fn with_max_neighbors(max_neighbors: Option<usize>) -> Self {
+        Self {
+            map: BTreeMap::new(),
+            max_neighbors,
+            truncated: false,
+        }
     }
 
     pub fn add_pair(&mut self, p1: ID, p2: ID) {
+        if let Some(max) = self.max_neighbors {
+            let is_full = |p, other| {
+                self.map
+                    .get(&p)
+                    .is_some_and(|s: &BTreeSet<ID>| s.len() >= max && !s.contains(&other))
+            };
+            if is_full(p1, p2) || is_full(p2, p1) {
+                self.truncated = true;
+                return;
+            }
+        }
         self.map.entry(p1).or_default().insert(p2);
         self.map.entry(p2).or_default().insert(p1);
     }
 
+    /// Whether some pairs were dropped because a particle reached the maximum amount of neighbors.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
     pub fn has_pair(&self, p1: ID, p2: ID) -> bool {
         self.map.get(&p1).is_some_and(|s| s.contains(&p2))
     }
 
+    pub fn pair_count(&self) -> usize {
+        self.map.values().map(BTreeSet::len).sum::<usize>() / 2
+    }
+
     pub fn get_neighbors(&self, p1: ID) -> impl Iterator<Item = &ID> {
         self.map
             .get(&p1)
@@ -39,6 +85,110 @@ impl<ID: Hash + Ord + Eq + Copy> NeighborMap<ID> {
     }
 }
 
+/// `NeighborMap` that also keeps the distance between the borders of every pair, so it doesn't
+/// have to be calculated again.
+#[derive(Debug, Default)]
+pub struct DistanceMap<ID> {
+    neighbors: NeighborMap<ID>,
+    distances: BTreeMap<(ID, ID), f64>,
+}
+
+impl<ID: Hash + Ord + Eq + Copy> DistanceMap<ID> {
+    pub fn with_max_neighbors(max_neighbors: Option<usize>) -> Self {
+        Self {
+            neighbors: NeighborMap::with_max_neighbors(max_neighbors),
+            distances: BTreeMap::new(),
+        }
+    }
+
+    fn key(p1: ID, p2: ID) -> (ID, ID) {
+        (p1.min(p2), p1.max(p2))
+    }
+
+    pub fn add_pair(&mut self, p1: ID, p2: ID, distance: f64) {
+        self.neighbors.add_pair(p1, p2);
+        if self.neighbors.has_pair(p1, p2) {
+            self.distances.insert(Self::key(p1, p2), distance);
+        }
+    }
+
+    pub fn neighbors(&self) -> &NeighborMap<ID> {
+        &self.neighbors
+    }
+
+    pub fn into_neighbors(self) -> NeighborMap<ID> {
+        self.neighbors
+    }
+
+    pub fn distance(&self, p1: ID, p2: ID) -> Option<f64> {
+        self.distances.get(&Self::key(p1, p2)).copied()
+    }
+
+    /// Neighbors of `p1` in ascending id order, along with their distance to it.
+    pub fn get_neighbors(&self, p1: ID) -> impl Iterator<Item = (ID, f64)> + '_ {
+        self.neighbors
+            .get_neighbors(p1)
+            .map(move |&p2| (p2, self.distances[&Self::key(p1, p2)]))
+    }
+}
+
+impl NeighborMap<usize> {
+    /// Adjacency matrix where particle `id` is row and column `id`, so every id must be less than
+    /// `n`. For sparse ids use `to_adjacency_matrix_of`.
+    pub fn to_adjacency_matrix(&self, n: usize) -> Array2<bool> {
+        let mut matrix = Array2::from_elem((n, n), false);
+        for (&particle, neighbors) in &self.map {
+            for &neighbor in neighbors {
+                matrix[(particle, neighbor)] = true;
+            }
+        }
+        matrix
+    }
+
+    /// Pairs that are only neighbors through the periodic boundary, that is, their direct distance
+    /// is bigger than `interaction_radius`. Always empty for maps built in non cyclic mode.
+    pub fn boundary_crossing_pairs<P: CircularParticle>(
+        &self,
+        particles: &[P],
+        interaction_radius: f64,
+    ) -> NeighborMap<usize> {
+        let particles: BTreeMap<_, _> = particles.iter().map(|p| (p.get_id(), p)).collect();
+        let mut map = NeighborMap::default();
+        for (&id, neighbors) in &self.map {
+            for &other in neighbors.iter().filter(|&&other| other > id) {
+                if !particles[&id].is_within_distance_of(
+                    particles[&other],
+                    interaction_radius,
+                    0.0,
+                    0.0,
+                    false,
+                ) {
+                    map.add_pair(id, other);
+                }
+            }
+        }
+        map
+    }
+}
+
+impl<ID: Hash + Ord + Eq + Copy> NeighborMap<ID> {
+    /// Adjacency matrix where the particle `ids[i]` is row and column `i`. Particles not in `ids`
+    /// are left out.
+    pub fn to_adjacency_matrix_of(&self, ids: &[ID]) -> Array2<bool> {
+        let index: BTreeMap<_, _> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let mut matrix = Array2::from_elem((ids.len(), ids.len()), false);
+        for (particle, neighbors) in &self.map {
+            let Some(&i) = index.get(particle) else {
+                continue;
+            };
+            for &j in neighbors.iter().filter_map(|n| index.get(n)) {
+                matrix[(i, j)] = true;
+            }
+        }
+        matrix
+    }
+}
+
 impl<ID: ToString> Display for NeighborMap<ID> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (particle, neighbors) in &self.map {
@@ -54,3 +204,60 @@ impl<ID: ToString> Display for NeighborMap<ID> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cim_finder::CimNeighborFinder,
+        fixtures::{cim_system, disk},
+    };
+
+    fn map(pairs: &[(usize, usize)]) -> NeighborMap<usize> {
+        let mut map = NeighborMap::default();
+        for &(p1, p2) in pairs {
+            map.add_pair(p1, p2);
+        }
+        map
+    }
+
+    #[test]
+    fn adjacency_matrix_is_symmetric_with_the_pairs() {
+        let matrix = map(&[(0, 1), (1, 3)]).to_adjacency_matrix(4);
+
+        assert_eq!(matrix.dim(), (4, 4));
+        for (i, j) in [(0, 1), (1, 0), (1, 3), (3, 1)] {
+            assert!(matrix[(i, j)]);
+        }
+        assert_eq!(matrix.iter().filter(|&&adjacent| adjacent).count(), 4);
+    }
+
+    #[test]
+    fn sparse_ids_are_indexed_in_the_given_order() {
+        let matrix = map(&[(10, 30), (30, 50)]).to_adjacency_matrix_of(&[50, 30]);
+
+        assert_eq!(matrix.dim(), (2, 2));
+        assert!(matrix[(0, 1)] && matrix[(1, 0)]);
+        assert!(!matrix[(0, 0)] && !matrix[(1, 1)]);
+    }
+
+    #[test]
+    fn straddling_pairs_cross_the_boundary_only_when_cyclic() {
+        // 0 and 1 are close through the left and right borders, 1 and 2 are close directly.
+        let particles = [
+            disk(0, 0.2, 5.0, 0.1),
+            disk(1, 9.8, 5.0, 0.1),
+            disk(2, 9.2, 5.0, 0.1),
+        ];
+
+        for cyclic in [false, true] {
+            let neighbors =
+                CimNeighborFinder::find_neighbors(&particles, cim_system(10.0, 5, 0.5, cyclic));
+            let crossing = neighbors.boundary_crossing_pairs(&particles, 0.5);
+
+            assert!(neighbors.has_pair(1, 2));
+            assert_eq!(crossing.has_pair(0, 1), cyclic);
+            assert_eq!(crossing.pair_count(), usize::from(cyclic));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use nalgebra::Vector2;
+use nalgebra::{Vector2, Vector3};
 
 pub type ID = usize;
 
@@ -14,6 +14,11 @@ pub trait CircularParticle: Clone + Copy {
         space_height: f64,
         cyclic: bool,
     ) -> bool {
+        self.distance_to(other, space_width, space_height, cyclic) <= radius
+    }
+
+    /// Distance between the borders of both particles, negative when they overlap.
+    fn distance_to(&self, other: &Self, space_width: f64, space_height: f64, cyclic: bool) -> f64 {
         let mut delta = self.get_position() - other.get_position();
         if cyclic {
             delta = delta.abs();
@@ -24,6 +29,27 @@ pub trait CircularParticle: Clone + Copy {
                 delta.y = space_height - delta.y;
             }
         }
-        delta.magnitude_squared() <= (radius + self.get_radius() + other.get_radius()).powi(2)
+        delta.magnitude() - self.get_radius() - other.get_radius()
+    }
+}
+
+pub trait SphericalParticle: Clone + Copy {
+    fn get_id(&self) -> ID;
+    fn get_position(&self) -> Vector3<f64>;
+    fn get_radius(&self) -> f64;
+
+    /// Distance between the borders of both particles, negative when they overlap. `space_size`
+    /// is only used when `cyclic` is set.
+    fn distance_to(&self, other: &Self, space_size: Vector3<f64>, cyclic: bool) -> f64 {
+        let mut delta = self.get_position() - other.get_position();
+        if cyclic {
+            delta = delta.abs();
+            for i in 0..3 {
+                if delta[i] > 0.5 * space_size[i] {
+                    delta[i] = space_size[i] - delta[i];
+                }
+            }
+        }
+        delta.magnitude() - self.get_radius() - other.get_radius()
     }
 }
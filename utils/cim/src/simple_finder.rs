@@ -1,7 +1,7 @@
 use itertools::Itertools;
 
 use crate::{
-    neighbor_finder::{NeighborFinder, NeighborMap},
+    neighbor_finder::{DistanceMap, NeighborFinder, NeighborMap},
     particles::{CircularParticle, ID},
 };
 
@@ -12,23 +12,35 @@ pub struct SystemInfo {
     pub interaction_radius: f64,
     pub space_width: f64,
     pub space_height: f64,
+    pub max_neighbors: Option<usize>,
 }
 
 impl<P: CircularParticle> NeighborFinder<P, SystemInfo> for SimpleNeighborFinder {
     fn find_neighbors(particles: &[P], system: SystemInfo) -> NeighborMap<ID> {
-        let mut map = NeighborMap::default();
-        for (p1, p2) in particles.iter().tuple_combinations() {
-            if p1.is_within_distance_of(
-                p2,
-                system.interaction_radius,
-                system.space_width,
-                system.space_height,
-                system.cyclic,
-            ) {
-                map.add_pair(p1.get_id(), p2.get_id());
-            }
-        }
+        let mut map = NeighborMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, _| map.add_pair(p1, p2));
+        map
+    }
 
+    fn find_neighbors_with_distance(particles: &[P], system: SystemInfo) -> DistanceMap<ID> {
+        let mut map = DistanceMap::with_max_neighbors(system.max_neighbors);
+        visit_pairs(particles, &system, |p1, p2, distance| {
+            map.add_pair(p1, p2, distance)
+        });
         map
     }
 }
+
+/// Calls `visit` with the ids and distance of every pair within the interaction radius.
+fn visit_pairs<P: CircularParticle>(
+    particles: &[P],
+    system: &SystemInfo,
+    mut visit: impl FnMut(ID, ID, f64),
+) {
+    for (p1, p2) in particles.iter().tuple_combinations() {
+        let distance = p1.distance_to(p2, system.space_width, system.space_height, system.cyclic);
+        if distance <= system.interaction_radius {
+            visit(p1.get_id(), p2.get_id(), distance);
+        }
+    }
+}
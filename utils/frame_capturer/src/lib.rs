@@ -129,6 +129,11 @@ impl FrameCapturer {
         self.frame_count += 1;
     }
 
+    /// Amount of frames rendered so far.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
     pub fn get_draw(&self) -> &Draw {
         &self.draw
     }
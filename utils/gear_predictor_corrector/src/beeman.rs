@@ -0,0 +1,82 @@
+use num_traits::Float;
+use std::ops::{Add, Mul, Sub};
+
+fn constant<F: Float>(value: f64) -> F {
+    F::from(value).unwrap()
+}
+
+/// What Beeman's method needs to remember between steps, besides the current acceleration.
+#[derive(Debug, Clone, Copy)]
+pub struct BeemanState<T> {
+    pub prev_acc: T,
+}
+
+impl<T: Copy + Add<T, Output = T> + Sub<T, Output = T>> BeemanState<T> {
+    /// Position after `dt` given the current position, velocity and acceleration.
+    pub fn predict_position<F: Float>(&self, p: T, v: T, a: T, dt: F) -> T
+    where
+        T: Mul<F, Output = T>,
+    {
+        p + v * dt + (a * constant(2.0 / 3.0) - self.prev_acc * constant(1.0 / 6.0)) * (dt * dt)
+    }
+
+    /// Velocity after `dt` given the acceleration at the start of the step and the one calculated
+    /// at the predicted position. Remembers `a` as the previous acceleration for the next step.
+    pub fn correct_velocity<F: Float>(&mut self, v: T, a: T, a_new: T, dt: F) -> T
+    where
+        T: Mul<F, Output = T>,
+    {
+        let next_v = v
+            + (a_new * constant(1.0 / 3.0) + a * constant(5.0 / 6.0)
+                - self.prev_acc * constant(1.0 / 6.0))
+                * dt;
+        self.prev_acc = a;
+        next_v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn bouncing_ball_period_matches_the_analytic_one() {
+        const LENGTH: f64 = 10.0;
+        const RADIUS: f64 = 0.5;
+        const SPEED: f64 = 1.0;
+        // Stiffness of the walls over the mass of the ball.
+        const K: f64 = 1e6;
+        const DT: f64 = 1e-4;
+        let acceleration = |x: f64| {
+            if x < RADIUS {
+                K * (RADIUS - x)
+            } else if x > LENGTH - RADIUS {
+                -K * (x - (LENGTH - RADIUS))
+            } else {
+                0.0
+            }
+        };
+
+        let (mut x, mut v, mut a) = (LENGTH / 2.0, SPEED, 0.0);
+        let mut state = BeemanState { prev_acc: 0.0 };
+        let mut time = 0.0;
+        // Times when the ball bounces off the left wall.
+        let mut bounces = vec![];
+        while bounces.len() < 3 {
+            let next_x = state.predict_position(x, v, a, DT);
+            let next_a = acceleration(next_x);
+            let next_v = state.correct_velocity(v, a, next_a, DT);
+            time += DT;
+            if v < 0.0 && next_v >= 0.0 {
+                bounces.push(time);
+            }
+            (x, v, a) = (next_x, next_v, next_a);
+        }
+
+        // Crossing the free length twice plus half an oscillation against each wall.
+        let expected = 2.0 * (LENGTH - 2.0 * RADIUS) / SPEED + 2.0 * PI / K.sqrt();
+        let period = bounces[2] - bounces[1];
+        assert!((period - expected).abs() < expected * 0.01);
+    }
+}
@@ -1,5 +1,8 @@
 use num_traits::Float;
-use std::{ops::{Add, Div, Mul, Sub}, fmt::Debug};
+use std::ops::{Add, Div, Mul, Sub};
+
+pub mod beeman;
+pub mod velocity_verlet;
 
 const fn fac(n: u64) -> u64 {
     match n {
@@ -13,61 +16,162 @@ fn fac_f<F: Float>(n: u64) -> F {
     F::from(fac(n)).unwrap()
 }
 
-pub struct GearPredictor<T> {
-    pub rs: [T; 6],
+const ORDER_3_COEFFICIENTS: [f64; 4] = [1.0 / 6.0, 5.0 / 6.0, 1.0, 1.0 / 3.0];
+const ORDER_4_COEFFICIENTS: [f64; 5] = [19.0 / 120.0, 3.0 / 4.0, 1.0, 1.0 / 2.0, 1.0 / 12.0];
+const ORDER_5_COEFFICIENTS: [f64; 6] = [
+    3.0 / 16.0,
+    251.0 / 360.0,
+    1.0,
+    11.0 / 18.0,
+    1.0 / 6.0,
+    1.0 / 60.0,
+];
+
+const fn corrector_coefficients<const N: usize>() -> [f64; N] {
+    let values: &[f64] = match N {
+        4 => &ORDER_3_COEFFICIENTS,
+        5 => &ORDER_4_COEFFICIENTS,
+        6 => &ORDER_5_COEFFICIENTS,
+        _ => panic!("Gear corrector coefficients are only known for orders 3 to 5"),
+    };
+    let mut coefficients = [0.0; N];
+    let mut i = 0;
+    while i < N {
+        coefficients[i] = values[i];
+        i += 1;
+    }
+    coefficients
 }
 
-pub struct GearCorrector<T> {
-    pub predictions: [T; 6],
+struct Coefficients<const N: usize>;
+
+impl<const N: usize> Coefficients<N> {
+    // Evaluated at compile time, so using an unsupported order fails to build.
+    const CORRECTOR: [f64; N] = corrector_coefficients::<N>();
 }
 
-impl<T: Copy + Sub<T, Output = T> + Add<T, Output = T>> GearPredictor<T> {
-    pub fn predict<F: Float + Debug>(self, dt: F) -> GearCorrector<T>
+/// Gear predictor of order `N - 1`, `rs` holds the position and its first `N - 1` derivatives.
+/// Orders 3 to 5 are supported. `T` can be any vector type (or a plain float) that can be added,
+/// subtracted and scaled by the float type used for the time step, like nalgebra's `Vector2` and
+/// `Vector3` with `f64` or glam's `Vec2` and `Vec3` with `f32`.
+pub struct GearPredictor<T, const N: usize = 6> {
+    pub rs: [T; N],
+}
+
+pub struct GearCorrector<T, const N: usize = 6> {
+    pub predictions: [T; N],
+}
+
+pub type Gear3Predictor<T> = GearPredictor<T, 4>;
+pub type Gear4Predictor<T> = GearPredictor<T, 5>;
+pub type Gear5Predictor<T> = GearPredictor<T, 6>;
+
+impl<T: Copy + Sub<T, Output = T> + Add<T, Output = T>, const N: usize> GearPredictor<T, N> {
+    pub fn predict<F: Float>(self, dt: F) -> GearCorrector<T, N>
     where
-        T: Div<F, Output = T> + Mul<F, Output = T> + Debug,
+        T: Div<F, Output = T> + Mul<F, Output = T>,
     {
-        let [r, r1, r2, r3, r4, r5] = self.rs;
-
         GearCorrector {
-            predictions: [
-                r + r1 * dt
-                    + r2 * dt.powi(2) / fac_f(2)
-                    + r3 * dt.powi(3) / fac_f(3)
-                    + r4 * dt.powi(4) / fac_f(4)
-                    + r5 * dt.powi(5) / fac_f(5),
-                r1 + r2 * dt
-                    + r3 * dt.powi(2) / fac_f(2)
-                    + r4 * dt.powi(3) / fac_f(3)
-                    + r5 * dt.powi(4) / fac_f(4),
-                r2 + r3 * dt + r4 * dt.powi(2) / fac_f(2) + r5 * dt.powi(3) / fac_f(3),
-                r3 + r4 * dt + r5 * dt.powi(2) / fac_f(2),
-                r4 + r5 * dt,
-                r5,
-            ],
+            predictions: std::array::from_fn(|i| {
+                (i + 1..N).fold(self.rs[i], |prediction, j| {
+                    prediction + self.rs[j] * dt.powi((j - i) as i32) / fac_f((j - i) as u64)
+                })
+            }),
         }
     }
 }
 
-impl<T: Copy + Sub<T, Output = T> + Add<T, Output = T> + Debug> GearCorrector<T> {
-    pub fn correct<F: Float + Debug>(&self, r2: T, dt: F) -> [T; 6]
+impl<T: Copy + Sub<T, Output = T> + Add<T, Output = T>, const N: usize> GearCorrector<T, N> {
+    pub fn correct<F: Float>(&self, r2: T, dt: F) -> [T; N]
     where
         T: Div<F, Output = T> + Mul<F, Output = T>,
         F: Mul<T, Output = T>,
     {
-        let [rp, r1p, r2p, r3p, r4p, r5p] = self.predictions;
-
         // Calculate the deltas
-        let da = r2 - r2p;
+        let da = r2 - self.predictions[2];
         let dr2 = da * dt.powi(2) / fac_f(2); // delta r2
 
         // Calculate the corrections
-        [
-            rp + F::from(3.0 / 16.0).unwrap() * dr2,
-            r1p + F::from(251.0 / 360.0).unwrap() * dr2 / dt,
-            r2p + F::from(1.0).unwrap() * dr2 * fac_f(2) / dt.powi(2),
-            r3p + F::from(11.0 / 18.0).unwrap() * dr2 * fac_f(3) / dt.powi(3),
-            r4p + F::from(1.0 / 6.0).unwrap() * dr2 * fac_f(4) / dt.powi(4),
-            r5p + F::from(1.0 / 60.0).unwrap() * dr2 * fac_f(5) / dt.powi(5),
-        ]
+        std::array::from_fn(|i| {
+            self.predictions[i]
+                + F::from(Coefficients::<N>::CORRECTOR[i]).unwrap() * dr2 * fac_f(i as u64)
+                    / dt.powi(i as i32)
+        })
+    }
+
+    /// Same as `correct`, also returning the correction applied to the highest order derivative,
+    /// whose magnitude can be used as an error estimate for `suggest_dt`.
+    pub fn correct_with_error<F: Float>(&self, r2: T, dt: F) -> ([T; N], T)
+    where
+        T: Div<F, Output = T> + Mul<F, Output = T>,
+        F: Mul<T, Output = T>,
+    {
+        let corrected = self.correct(r2, dt);
+        let error = corrected[N - 1] - self.predictions[N - 1];
+        (corrected, error)
+    }
+}
+
+/// Time step for the next step of a Gear integrator of order `N - 1`, given the magnitude of the
+/// last correction. Grows the step when the error is below `tolerance` and shrinks it otherwise,
+/// changing it at most by a factor of 5 at a time.
+pub fn suggest_dt<F: Float, const N: usize>(error: F, tolerance: F, dt: F) -> F {
+    let safety = F::from(0.9).unwrap();
+    let max_factor = F::from(5.0).unwrap();
+    if error <= F::zero() {
+        return dt * max_factor;
+    }
+    let factor = safety * (tolerance / error).powf(F::one() / F::from(N).unwrap());
+    dt * factor.max(F::one() / max_factor).min(max_factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+    use nalgebra::Vector2;
+
+    /// Integrates a unit harmonic oscillator, `a = -x`, with the given vector type.
+    fn oscillate<T, F: Float>(start: T, dt: F, steps: usize) -> Vec<T>
+    where
+        T: Copy + Add<T, Output = T> + Sub<T, Output = T> + Div<F, Output = T> + Mul<F, Output = T>,
+        F: Mul<T, Output = T>,
+    {
+        let zero = start * F::zero();
+        let mut rs = [zero; 6];
+        rs[0] = start;
+        rs[2] = start * -F::one();
+        rs[4] = start;
+        (0..steps)
+            .map(|_| {
+                let corrector = Gear5Predictor { rs }.predict(dt);
+                rs = corrector.correct(corrector.predictions[0] * -F::one(), dt);
+                rs[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nalgebra_and_glam_vectors_follow_the_same_trajectory() {
+        let with_nalgebra = oscillate(Vector2::new(1.0, -0.5), 1e-3_f64, 2000);
+        let with_glam = oscillate(Vec2::new(1.0, -0.5), 1e-3_f32, 2000);
+
+        // Only as close as the single precision of glam allows.
+        for (a, b) in with_nalgebra.iter().zip(&with_glam) {
+            assert!((a - Vector2::new(b.x as f64, b.y as f64)).magnitude() < 1e-3);
+        }
+        // Two seconds of `x = cos(t)`.
+        let last = with_nalgebra.last().unwrap();
+        assert!((last.x - 2.0_f64.cos()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn suggested_dt_shrinks_when_the_error_exceeds_the_tolerance() {
+        let dt = 1e-3;
+        assert!(suggest_dt::<f64, 6>(1e-4, 1e-6, dt) < dt);
+        assert!(suggest_dt::<f64, 6>(1e-8, 1e-6, dt) > dt);
+        // Never by more than a factor of 5.
+        assert!((suggest_dt::<f64, 6>(1e10, 1e-6, dt) - dt / 5.0).abs() < 1e-15);
+        assert_eq!(suggest_dt::<f64, 6>(0.0, 1e-6, dt), dt * 5.0);
     }
 }
@@ -0,0 +1,53 @@
+use num_traits::Float;
+use std::ops::{Add, Mul};
+
+/// Position after `dt` given the current position, velocity and acceleration.
+pub fn velocity_verlet_position<T, F: Float>(p: T, v: T, a: T, dt: F) -> T
+where
+    T: Copy + Add<T, Output = T> + Mul<F, Output = T>,
+{
+    p + v * dt + a * (dt * dt / F::from(2.0).unwrap())
+}
+
+/// Velocity after `dt` given the acceleration at the start and at the end of the step.
+pub fn velocity_verlet_velocity<T, F: Float>(v: T, a_old: T, a_new: T, dt: F) -> T
+where
+    T: Copy + Add<T, Output = T> + Mul<F, Output = T>,
+{
+    v + (a_old + a_new) * (dt / F::from(2.0).unwrap())
+}
+
+/// Advances a single body by `dt`. `a_new` calculates the acceleration at the new position. When
+/// the acceleration depends on other bodies, first move every body with `velocity_verlet_position`
+/// and then update the velocities with `velocity_verlet_velocity`.
+pub fn velocity_verlet<T, F: Float, A: FnOnce(T) -> T>(
+    p: T,
+    v: T,
+    a_old: T,
+    a_new: A,
+    dt: F,
+) -> (T, T)
+where
+    T: Copy + Add<T, Output = T> + Mul<F, Output = T>,
+{
+    let next_p = velocity_verlet_position(p, v, a_old, dt);
+    let next_v = velocity_verlet_velocity(v, a_old, a_new(next_p), dt);
+    (next_p, next_v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harmonic_oscillator_energy_stays_bounded() {
+        let energy = |p: f64, v: f64| (p * p + v * v) / 2.0;
+        let (mut p, mut v) = (1.0, 0.0);
+        let initial_energy = energy(p, v);
+
+        for _ in 0..10_000 {
+            (p, v) = velocity_verlet(p, v, -p, |p| -p, 0.01);
+            assert!((energy(p, v) - initial_energy).abs() < initial_energy * 1e-3);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    mem,
+};
+
+use nalgebra::Vector2;
+
+use crate::{
+    models::{Ball, Frame, IterableFrame},
+    Float,
+};
+
+impl<'a, I: ExactSizeIterator<Item = &'a Ball>> IterableFrame<I> {
+    /// Writes the frame in little endian as the time (`Float`), the amount of balls (`u32`) and
+    /// then `id` (`u32`), `x`, `y`, `vx` and `vy` (`Float`) for each ball. `Float` is `f64`
+    /// unless built with the `use_f32` feature, so both ends must agree on it.
+    pub fn write_binary_to<W: Write>(self, f: &mut W) -> std::io::Result<()> {
+        f.write_all(&self.time.to_le_bytes())?;
+        f.write_all(&(self.balls.len() as u32).to_le_bytes())?;
+        for ball in self.balls {
+            f.write_all(&(ball.id as u32).to_le_bytes())?;
+            for value in [
+                ball.position.x,
+                ball.position.y,
+                ball.velocity.x,
+                ball.velocity.y,
+            ] {
+                f.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_u32<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_float<R: Read>(r: &mut R) -> std::io::Result<Float> {
+    let mut bytes = [0; mem::size_of::<Float>()];
+    r.read_exact(&mut bytes)?;
+    Ok(Float::from_le_bytes(bytes))
+}
+
+/// See `IterableFrame::write_binary_to` for the layout.
+pub fn write_binary<W: Write>(frame: &Frame, f: &mut W) -> std::io::Result<()> {
+    IterableFrame::from(frame).write_binary_to(f)
+}
+
+/// Reads a frame written by `write_binary`, `None` if the reader is already at its end.
+pub fn read_binary<R: Read>(r: &mut R) -> std::io::Result<Option<Frame>> {
+    let time = match read_float(r) {
+        Ok(time) => time,
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let count = read_u32(r)?;
+    let balls = (0..count)
+        .map(|_| {
+            let id = read_u32(r)? as usize;
+            let [x, y, vx, vy] = [
+                read_float(r)?,
+                read_float(r)?,
+                read_float(r)?,
+                read_float(r)?,
+            ];
+            Ok(Ball {
+                id,
+                position: Vector2::new(x, y),
+                velocity: Vector2::new(vx, vy),
+                radius: 0.0,
+                angular_velocity: 0.0,
+            })
+        })
+        .collect::<std::io::Result<_>>()?;
+    Ok(Some(Frame {
+        time,
+        particles: balls,
+    }))
+}
+
+/// Frames of an output written with `--output-format binary`.
+pub fn binary_output_parser<R: Read>(mut r: R) -> impl Iterator<Item = Frame> {
+    std::iter::from_fn(move || read_binary(&mut r).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Frame with `count` balls at random places. Only what the binary format keeps is set.
+    fn random_frame(rng: &mut StdRng, count: usize) -> Frame {
+        let time = rng.gen();
+        let mut vector =
+            || Vector2::new(rng.gen_range(-100.0..100.0), rng.gen_range(-100.0..100.0));
+        Frame {
+            time,
+            particles: (0..count)
+                .map(|id| Ball {
+                    id,
+                    position: vector(),
+                    velocity: vector(),
+                    radius: 0.0,
+                    angular_velocity: 0.0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let frames = [0, 1, 16, 0, 200].map(|count| random_frame(&mut rng, count));
+
+        let mut bytes = vec![];
+        for frame in &frames {
+            write_binary(frame, &mut bytes).unwrap();
+        }
+
+        let mut reader = bytes.as_slice();
+        for frame in &frames {
+            let read = read_binary(&mut reader).unwrap().unwrap();
+            assert_eq!(read.time, frame.time);
+            assert_eq!(read.particles, frame.particles);
+        }
+        assert!(read_binary(&mut reader).unwrap().is_none());
+    }
+}
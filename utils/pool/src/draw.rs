@@ -1,6 +1,5 @@
 use std::num::ParseIntError;
 
-use nalgebra::Vector2;
 use nannou::{
     color::{rgb_u32, Saturate, Shade},
     prelude::{Rgb, *},
@@ -8,7 +7,7 @@ use nannou::{
 
 use crate::{
     models::{Ball, InputData},
-    Float,
+    Hole,
 };
 
 fn parse_hex_color(s: &str) -> Result<Rgb<u8>, ParseIntError> {
@@ -18,7 +17,7 @@ fn parse_hex_color(s: &str) -> Result<Rgb<u8>, ParseIntError> {
 pub fn draw<BI: IntoIterator<Item = Ball>>(
     system_info: &InputData,
     balls: BI,
-    holes: &[Vector2<Float>],
+    holes: &[Hole],
     draw: &Draw,
 ) {
     let draw = draw.scale(1.0 / system_info.table_height as f32);
@@ -54,9 +53,9 @@ pub fn draw<BI: IntoIterator<Item = Ball>>(
 
     for hole in holes {
         draw.ellipse()
-            .radius(system_info.hole_radius as f32)
-            .x(hole.x as f32)
-            .y(hole.y as f32)
+            .radius(hole.radius as f32)
+            .x(hole.position.x as f32)
+            .y(hole.position.y as f32)
             //.no_fill()
             //.stroke_weight(1.0)
             //.stroke(GRAY)
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use cim::particles::ID;
+use itertools::Itertools;
+use nalgebra::Vector2;
+
+use crate::{
+    models::{Ball, InputData},
+    Float, Hole,
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Collision {
+    pub time: Float,
+    pub info: CollisionAgainst,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum WallType {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum CollisionAgainst {
+    Ball(ID, ID),
+    Wall(ID, WallType),
+    /// Ball id and index of the hole in the slice given to `find_earliest_collision`.
+    Hole(ID, usize),
+}
+
+/// Earliest time in which the gap between the two balls reaches zero, `None` if they never touch or are
+/// moving away from each other. Balls that already overlap and are approaching collide immediately.
+pub fn find_collision_between_balls(b1: &Ball, b2: &Ball, radius_sum: Float) -> Option<Float> {
+    let delta_v = b2.velocity - b1.velocity;
+    let delta_r = b2.position - b1.position;
+    let sigma = radius_sum;
+    let dv_dr = delta_v.dot(&delta_r);
+    if dv_dr >= 0.0 {
+        return None;
+    }
+
+    let dr_dr = delta_r.dot(&delta_r);
+    if dr_dr <= sigma.powi(2) {
+        return Some(0.0);
+    }
+
+    let dv_dv = delta_v.dot(&delta_v);
+    let d = dv_dr.powi(2) - dv_dv * (dr_dr - sigma.powi(2));
+
+    (d >= 0.0).then(|| -(dv_dr + d.sqrt()) / dv_dv)
+}
+
+/// Earliest time in which the ball touches the wall it's moving towards, `None` if it isn't moving.
+/// A ball that is already past a wall and still moving into it collides immediately.
+pub fn find_collision_against_wall(ball: &Ball, config: &InputData) -> Option<(Float, WallType)> {
+    let radius = config.ball_radius;
+
+    let time_x = if ball.velocity.x > 0.0 {
+        Some((config.table_width - radius - ball.position.x) / ball.velocity.x)
+    } else if ball.velocity.x < 0.0 {
+        Some((radius - ball.position.x) / ball.velocity.x)
+    } else {
+        None
+    }
+    .map(|t| (t.max(0.0), WallType::Vertical));
+
+    let time_y = if ball.velocity.y > 0.0 {
+        Some((config.table_height - radius - ball.position.y) / ball.velocity.y)
+    } else if ball.velocity.y < 0.0 {
+        Some((radius - ball.position.y) / ball.velocity.y)
+    } else {
+        None
+    }
+    .map(|t| (t.max(0.0), WallType::Horizontal));
+
+    time_x
+        .into_iter()
+        .chain(time_y.into_iter())
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+}
+
+/// Next collision among `state`, `None` if no ball will ever hit anything.
+pub fn find_earliest_collision(
+    state: &[&Ball],
+    holes: &[Hole],
+    config: &InputData,
+) -> Option<Collision> {
+    let mut earliest: Option<Collision> = None;
+    let mut update = |time: Float, info: CollisionAgainst| {
+        if earliest.map_or(true, |e| time < e.time) {
+            earliest = Some(Collision { time, info });
+        }
+    };
+
+    for (ball_1, ball_2) in state.iter().tuple_combinations() {
+        if let Some(time) = find_collision_between_balls(ball_1, ball_2, config.ball_radius * 2.0) {
+            update(time, CollisionAgainst::Ball(ball_1.id, ball_2.id));
+        }
+    }
+
+    for (ball, (index, hole)) in state.iter().cartesian_product(holes.iter().enumerate()) {
+        let hole_ball = Ball {
+            id: 0,
+            position: hole.position,
+            velocity: Vector2::zeros(),
+            radius: 0.0,
+            angular_velocity: 0.0,
+        };
+        if let Some(time) =
+            find_collision_between_balls(ball, &hole_ball, config.ball_radius + hole.radius)
+        {
+            update(time, CollisionAgainst::Hole(ball.id, index));
+        }
+    }
+
+    for ball in state.iter() {
+        if let Some((time, wall_type)) = find_collision_against_wall(ball, config) {
+            update(time, CollisionAgainst::Wall(ball.id, wall_type));
+        }
+    }
+
+    earliest
+}
+
+pub fn apply_collision(state: &mut BTreeMap<ID, Ball>, config: &InputData, collision: Collision) {
+    match collision.info {
+        CollisionAgainst::Ball(id1, id2) => {
+            let delta_v = state[&id2].velocity - state[&id1].velocity;
+            let delta_r = state[&id2].position - state[&id1].position;
+            let sigma = config.ball_radius * 2.0;
+
+            let j = (2.0 * config.ball_mass.powi(2) * (delta_v.dot(&delta_r)))
+                / (sigma * (config.ball_mass * 2.0));
+            let j_vec = delta_r * j / sigma;
+
+            let ball_1 = state.get_mut(&id1).unwrap();
+            ball_1.velocity += j_vec / config.ball_mass;
+            let ball_2 = state.get_mut(&id2).unwrap();
+            ball_2.velocity -= j_vec / config.ball_mass;
+        }
+        CollisionAgainst::Wall(id, wall_type) => match wall_type {
+            WallType::Horizontal => state.get_mut(&id).unwrap().velocity.y *= -config.restitution,
+            WallType::Vertical => state.get_mut(&id).unwrap().velocity.x *= -config.restitution,
+        },
+        CollisionAgainst::Hole(id, _) => {
+            state.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{parse, COASTING_BALL, TWO_BALL_COLLISION};
+
+    fn ball(id: ID, x: Float, y: Float, vx: Float, vy: Float) -> Ball {
+        Ball {
+            id,
+            position: Vector2::new(x, y),
+            velocity: Vector2::new(vx, vy),
+            radius: 1.0,
+            angular_velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn head_on_balls_collide_when_the_gap_closes() {
+        let time = find_collision_between_balls(
+            &ball(0, 0.0, 0.0, 1.0, 0.0),
+            &ball(1, 20.0, 0.0, -1.0, 0.0),
+            2.0,
+        );
+        assert_eq!(time, Some(9.0));
+    }
+
+    #[test]
+    fn glancing_balls_collide_only_within_the_radius_sum() {
+        let moving = ball(0, 0.0, 0.0, 1.0, 0.0);
+
+        let time = find_collision_between_balls(&moving, &ball(1, 10.0, 1.5, 0.0, 0.0), 2.0);
+        assert!((time.unwrap() - (10.0 - 1.75_f64.sqrt())).abs() < 1e-9);
+        assert_eq!(
+            find_collision_between_balls(&moving, &ball(1, 10.0, 2.5, 0.0, 0.0), 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn diverging_balls_never_collide() {
+        let time = find_collision_between_balls(
+            &ball(0, 0.0, 0.0, -1.0, 0.0),
+            &ball(1, 20.0, 0.0, 1.0, 0.0),
+            2.0,
+        );
+        assert_eq!(time, None);
+    }
+
+    #[test]
+    fn overlapping_approaching_balls_collide_immediately() {
+        let time = find_collision_between_balls(
+            &ball(0, 0.0, 0.0, 1.0, 0.0),
+            &ball(1, 1.5, 0.0, 0.0, 0.0),
+            2.0,
+        );
+        assert_eq!(time, Some(0.0));
+    }
+
+    #[test]
+    fn balls_hit_the_wall_they_move_towards() {
+        let config = parse(COASTING_BALL);
+        let radius = config.ball_radius;
+        let cases = [
+            (1.0, 0.0, config.table_width - radius - 100.0),
+            (-1.0, 0.0, 100.0 - radius),
+            (0.0, 1.0, config.table_height - radius - 50.0),
+            (0.0, -1.0, 50.0 - radius),
+        ];
+
+        for (vx, vy, expected) in cases {
+            let (time, wall) =
+                find_collision_against_wall(&ball(0, 100.0, 50.0, vx, vy), &config).unwrap();
+            assert!((time - expected).abs() < 1e-9);
+            if vx == 0.0 {
+                assert!(matches!(wall, WallType::Horizontal));
+            } else {
+                assert!(matches!(wall, WallType::Vertical));
+            }
+        }
+    }
+
+    #[test]
+    fn balls_moving_parallel_to_a_wall_never_hit_it() {
+        let config = parse(COASTING_BALL);
+        let along_the_bottom = ball(0, 100.0, config.ball_radius, 1.0, 0.0);
+
+        let (_, wall) = find_collision_against_wall(&along_the_bottom, &config).unwrap();
+        assert!(matches!(wall, WallType::Vertical));
+        assert!(find_collision_against_wall(&ball(0, 100.0, 50.0, 0.0, 0.0), &config).is_none());
+    }
+
+    #[test]
+    fn equal_masses_swap_velocities_head_on() {
+        let config = parse(TWO_BALL_COLLISION);
+        let mut state: BTreeMap<_, _> = config.balls.iter().map(|b| (b.id, *b)).collect();
+
+        let collision =
+            find_earliest_collision(&state.values().collect_vec(), &[], &config).unwrap();
+        assert!(matches!(collision.info, CollisionAgainst::Ball(0, 1)));
+        for ball in state.values_mut() {
+            *ball = ball.advance(collision.time);
+        }
+        apply_collision(&mut state, &config, collision);
+
+        assert!((state[&0].velocity - Vector2::new(-100.0, 0.0)).magnitude() < 1e-9);
+        assert!((state[&1].velocity - Vector2::new(100.0, 0.0)).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn wall_bounces_keep_the_restitution_of_the_speed() {
+        let mut config = parse(COASTING_BALL);
+        config.restitution = 0.5;
+        let mut state = BTreeMap::from([(0, ball(0, 100.0, 50.0, 4.0, -2.0))]);
+
+        let collision = |wall_type| Collision {
+            time: 0.0,
+            info: CollisionAgainst::Wall(0, wall_type),
+        };
+        apply_collision(&mut state, &config, collision(WallType::Vertical));
+        assert_eq!(state[&0].velocity, Vector2::new(-2.0, -2.0));
+        apply_collision(&mut state, &config, collision(WallType::Horizontal));
+        assert_eq!(state[&0].velocity, Vector2::new(-2.0, 1.0));
+    }
+}
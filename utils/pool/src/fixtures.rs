@@ -0,0 +1,100 @@
+use chumsky::Parser;
+
+use crate::{models::InputData, parser::input_parser};
+
+/// Two balls moving towards each other along the same line.
+pub const TWO_BALL_COLLISION: &str = "224
+112
+11.4
+5.7
+165
+2
+0 56.0 56.0 100 0
+1 168.0 56.0 -100 0
+";
+
+/// Two balls at rest overlapping by 1.4.
+pub const OVERLAPPING_PAIR: &str = "224
+112
+11.4
+5.7
+165
+2
+0 100.0 56.0 0 0
+1 104.3 56.0 0 0
+";
+
+/// A single ball heading into the bottom left corner.
+pub const INTO_THE_CORNER: &str = "224
+112
+11.4
+5.7
+165
+1
+0 30.0 30.0 -100 -100
+";
+
+/// A single ball moving diagonally on an empty table.
+pub const COASTING_BALL: &str = "224
+112
+11.4
+5.7
+165
+1
+0 112.0 56.0 50 25
+";
+
+/// Cue ball heading towards the standard 15 ball triangle.
+pub const RACK: &str = "224
+112
+11.4
+5.7
+165
+16
+0 56.0 56.0 200 0
+1 168.0 56.0 0 0
+2 173.42997928172844 52.865 0 0
+3 173.42997928172844 59.135 0 0
+4 178.85995856345687 49.73 0 0
+5 178.85995856345687 56.0 0 0
+6 178.85995856345687 62.27 0 0
+7 184.2899378451853 46.595 0 0
+8 184.2899378451853 52.865 0 0
+9 184.2899378451853 59.135 0 0
+10 184.2899378451853 65.405 0 0
+11 189.7199171269137 43.46 0 0
+12 189.7199171269137 49.73 0 0
+13 189.7199171269137 56.0 0 0
+14 189.7199171269137 62.27 0 0
+15 189.7199171269137 68.54 0 0
+";
+
+pub const ALL: [&str; 5] = [
+    TWO_BALL_COLLISION,
+    OVERLAPPING_PAIR,
+    INTO_THE_CORNER,
+    COASTING_BALL,
+    RACK,
+];
+
+/// Parses one of the fixtures, panicking if it's malformed.
+pub fn parse(fixture: &str) -> InputData {
+    input_parser()
+        .parse(fixture)
+        .into_result()
+        .expect("Error parsing fixture.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_fixtures_parse() {
+        for fixture in ALL {
+            let input = parse(fixture);
+            assert!(!input.balls.is_empty());
+            assert!(input.warnings(None).is_empty());
+        }
+    }
+}
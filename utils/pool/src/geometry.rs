@@ -0,0 +1,85 @@
+use nalgebra::Vector2;
+
+use crate::{models::InputData, Float};
+
+/// Position in table coordinates of a point given as fractions of the table size.
+pub fn scale_to_table(
+    normalized: Vector2<Float>,
+    table_width: Float,
+    table_height: Float,
+) -> Vector2<Float> {
+    normalized.component_mul(&Vector2::new(table_width, table_height))
+}
+
+/// Centers of the holes of `input` in table coordinates, in the order of its layout.
+pub fn scaled_holes(input: &InputData) -> Vec<Vector2<Float>> {
+    input
+        .hole_positions
+        .iter()
+        .map(|&position| scale_to_table(position, input.table_width, input.table_height))
+        .collect()
+}
+
+/// Whether `position` is inside any of the holes centered at `holes`, borders included.
+pub fn is_in_any_hole(
+    position: Vector2<Float>,
+    holes: &[Vector2<Float>],
+    hole_radius: Float,
+) -> bool {
+    holes
+        .iter()
+        .any(|hole| (hole - position).magnitude_squared() <= hole_radius.powi(2))
+}
+
+/// Closest position to `position` where a ball of `radius` doesn't go through the cushions of a
+/// `table_width` by `table_height` table.
+pub fn clamp_into_table(
+    position: Vector2<Float>,
+    radius: Float,
+    table_width: Float,
+    table_height: Float,
+) -> Vector2<Float> {
+    let min = Vector2::new(radius, radius);
+    let max = Vector2::new(table_width - radius, table_height - radius);
+    position.sup(&min).inf(&max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{parse, COASTING_BALL};
+
+    #[test]
+    fn holes_are_scaled_to_the_table() {
+        let holes = scaled_holes(&parse(COASTING_BALL));
+
+        assert_eq!(holes.len(), 6);
+        assert_eq!(holes[0], Vector2::new(0.0, 0.0));
+        assert_eq!(holes[3], Vector2::new(224.0, 112.0));
+        assert_eq!(holes[5], Vector2::new(112.0, 112.0));
+    }
+
+    #[test]
+    fn positions_in_corner_holes() {
+        let holes = scaled_holes(&parse(COASTING_BALL));
+
+        assert!(is_in_any_hole(Vector2::new(0.0, 0.0), &holes, 11.4));
+        assert!(is_in_any_hole(Vector2::new(220.0, 108.0), &holes, 11.4));
+        // Exactly on the border of the top right one.
+        assert!(is_in_any_hole(Vector2::new(224.0, 104.0), &holes, 8.0));
+        assert!(!is_in_any_hole(Vector2::new(12.0, 12.0), &holes, 11.4));
+        assert!(!is_in_any_hole(Vector2::new(56.0, 56.0), &holes, 11.4));
+    }
+
+    #[test]
+    fn positions_are_clamped_into_the_table() {
+        let clamp = |x, y| clamp_into_table(Vector2::new(x, y), 5.0, 224.0, 112.0);
+
+        assert_eq!(clamp(100.0, 50.0), Vector2::new(100.0, 50.0));
+        assert_eq!(clamp(-3.0, 50.0), Vector2::new(5.0, 50.0));
+        assert_eq!(clamp(230.0, 50.0), Vector2::new(219.0, 50.0));
+        // Past both cushions of a corner.
+        assert_eq!(clamp(-3.0, 200.0), Vector2::new(5.0, 107.0));
+        assert_eq!(clamp(300.0, -1.0), Vector2::new(219.0, 5.0));
+    }
+}
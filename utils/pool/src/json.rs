@@ -0,0 +1,75 @@
+use std::io::{BufRead, Lines, Write};
+
+use cim::particles::ID;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{Ball, Frame, IterableFrame},
+    Float,
+};
+
+#[derive(Serialize, Deserialize)]
+struct JsonBall {
+    id: ID,
+    x: Float,
+    y: Float,
+    vx: Float,
+    vy: Float,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonFrame {
+    time: Float,
+    balls: Vec<JsonBall>,
+}
+
+impl From<&Ball> for JsonBall {
+    fn from(ball: &Ball) -> Self {
+        Self {
+            id: ball.id,
+            x: ball.position.x,
+            y: ball.position.y,
+            vx: ball.velocity.x,
+            vy: ball.velocity.y,
+        }
+    }
+}
+
+impl From<JsonFrame> for Frame {
+    fn from(frame: JsonFrame) -> Self {
+        Self {
+            time: frame.time,
+            particles: frame
+                .balls
+                .into_iter()
+                .map(|b| Ball {
+                    id: b.id,
+                    position: Vector2::new(b.x, b.y),
+                    velocity: Vector2::new(b.vx, b.vy),
+                    radius: 0.0,
+                    angular_velocity: 0.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a, I: ExactSizeIterator<Item = &'a Ball>> IterableFrame<I> {
+    /// Writes the frame as a single line
+    /// `{"time":..,"balls":[{"id":..,"x":..,"y":..,"vx":..,"vy":..}]}`.
+    pub fn write_json_to<W: Write>(self, f: &mut W) -> std::io::Result<()> {
+        let frame = JsonFrame {
+            time: self.time,
+            balls: self.balls.map(JsonBall::from).collect(),
+        };
+        serde_json::to_writer(&mut *f, &frame)?;
+        f.write_all(b"\n")
+    }
+}
+
+pub fn json_output_parser<B: BufRead>(file: Lines<B>) -> impl Iterator<Item = Frame> {
+    file.map(Result::unwrap)
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str::<JsonFrame>(&line).unwrap().into())
+}
@@ -1,6 +1,14 @@
+use std::fmt::Display;
+
 use nalgebra::Vector2;
 
+pub mod binary;
 pub mod draw;
+pub mod events;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+pub mod geometry;
+pub mod json;
 pub mod models;
 pub mod parser;
 
@@ -9,11 +17,93 @@ pub type Float = f64;
 #[cfg(feature = "use_f32")]
 pub type Float = f32;
 
-pub const HOLE_POSITIONS: [Vector2<Float>; 6] = [
-    Vector2::new(0.0, 0.0),
-    Vector2::new(1.0, 0.0),
-    Vector2::new(0.0, 1.0),
-    Vector2::new(1.0, 1.0),
-    Vector2::new(0.5, 0.0),
-    Vector2::new(0.5, 1.0),
-];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoleKind {
+    Corner,
+    Side,
+}
+
+impl Display for HoleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HoleKind::Corner => "corner",
+            HoleKind::Side => "side",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hole {
+    pub kind: HoleKind,
+    pub position: Vector2<Float>,
+    pub radius: Float,
+}
+
+/// Hole positions of a six pocket table as fractions of the table size: the four corners followed
+/// by the two pockets in the middle of the long sides.
+pub fn six_pocket_layout() -> Vec<Vector2<Float>> {
+    vec![
+        Vector2::new(0.0, 0.0),
+        Vector2::new(1.0, 0.0),
+        Vector2::new(0.0, 1.0),
+        Vector2::new(1.0, 1.0),
+        Vector2::new(0.5, 0.0),
+        Vector2::new(0.5, 1.0),
+    ]
+}
+
+/// Holes at `positions`, given as fractions of the table size. Holes on a corner of the table use
+/// `corner_radius` and the rest `side_radius`.
+pub fn table_holes(
+    table_width: Float,
+    table_height: Float,
+    positions: &[Vector2<Float>],
+    corner_radius: Float,
+    side_radius: Float,
+) -> Vec<Hole> {
+    let is_edge = |v: Float| v == 0.0 || v == 1.0;
+
+    positions
+        .iter()
+        .map(|position| {
+            let kind = if is_edge(position.x) && is_edge(position.y) {
+                HoleKind::Corner
+            } else {
+                HoleKind::Side
+            };
+            Hole {
+                kind,
+                position: geometry::scale_to_table(*position, table_width, table_height),
+                radius: match kind {
+                    HoleKind::Corner => corner_radius,
+                    HoleKind::Side => side_radius,
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn six_pockets_are_labeled_and_placed() {
+        let holes = table_holes(224.0, 112.0, &six_pocket_layout(), 11.4, 5.7);
+
+        let expected = [
+            (HoleKind::Corner, 0.0, 0.0, 11.4),
+            (HoleKind::Corner, 224.0, 0.0, 11.4),
+            (HoleKind::Corner, 0.0, 112.0, 11.4),
+            (HoleKind::Corner, 224.0, 112.0, 11.4),
+            (HoleKind::Side, 112.0, 0.0, 5.7),
+            (HoleKind::Side, 112.0, 112.0, 5.7),
+        ];
+        assert_eq!(holes.len(), expected.len());
+        for (hole, (kind, x, y, radius)) in holes.iter().zip(expected) {
+            assert_eq!(hole.kind, kind);
+            assert_eq!(hole.position, Vector2::new(x, y));
+            assert_eq!(hole.radius, radius);
+        }
+    }
+}
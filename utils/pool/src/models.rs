@@ -1,16 +1,57 @@
-use std::{fmt::Display, io::Write};
+use std::io::Write;
 
 use cim::particles::{CircularParticle, ID};
 use nalgebra::Vector2;
+use simulation::frame::{write_frame, write_frame_fmt, FrameSerialize};
 
-use crate::Float;
+use crate::{
+    geometry::{is_in_any_hole, scaled_holes},
+    table_holes, Float, Hole, HoleKind,
+};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ball {
     pub id: ID,
     pub position: Vector2<Float>,
     pub velocity: Vector2<Float>,
     pub radius: Float,
+    /// Counterclockwise, in radians per unit of time.
+    pub angular_velocity: Float,
+}
+
+impl Ball {
+    /// The ball after moving with constant velocity for `dt`, ignoring any forces.
+    pub fn advance(&self, dt: Float) -> Ball {
+        Ball {
+            position: self.position + self.velocity * dt,
+            ..*self
+        }
+    }
+}
+
+impl FrameSerialize for Ball {
+    // Pocketed balls stop being written.
+    const WRITE_COUNT: bool = true;
+
+    fn write_line<W: std::fmt::Write>(&self, f: &mut W) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.id, self.position.x, self.position.y, self.velocity.x, self.velocity.y
+        )
+    }
+}
+
+/// Writes the ball with its angular velocity as an extra column, which `output_parser` reads back.
+pub struct WithSpin<'a>(pub &'a Ball);
+
+impl FrameSerialize for WithSpin<'_> {
+    const WRITE_COUNT: bool = true;
+
+    fn write_line<W: std::fmt::Write>(&self, f: &mut W) -> std::fmt::Result {
+        self.0.write_line(f)?;
+        write!(f, " {}", self.0.angular_velocity)
+    }
 }
 
 impl CircularParticle for Ball {
@@ -34,21 +75,108 @@ pub struct InputData {
     pub hole_radius: Float,
     pub ball_radius: Float,
     pub ball_mass: Float,
+    /// Fraction of the normal velocity a ball keeps when bouncing off a cushion.
+    pub restitution: Float,
+    /// Deceleration of a moving ball caused by the table, in length units per second squared.
+    pub friction: Float,
+    /// Hole positions as fractions of the table size, between 0 and 1.
+    pub hole_positions: Vec<Vector2<Float>>,
     pub balls: Vec<Ball>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Frame {
-    pub time: Float,
-    pub balls: Vec<Ball>,
-}
+impl InputData {
+    /// Input that continues from the state of `frame`, with the table and ball parameters of
+    /// `table` (usually the input of the run that wrote the frame).
+    pub fn from_frame(frame: Frame, table: &InputData) -> Self {
+        Self {
+            table_width: table.table_width,
+            table_height: table.table_height,
+            hole_radius: table.hole_radius,
+            ball_radius: table.ball_radius,
+            ball_mass: table.ball_mass,
+            restitution: table.restitution,
+            friction: table.friction,
+            hole_positions: table.hole_positions.clone(),
+            balls: frame
+                .particles
+                .into_iter()
+                .map(|ball| Ball {
+                    radius: table.ball_radius,
+                    ..ball
+                })
+                .collect(),
+        }
+    }
 
-impl Display for Frame {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        IterableFrame::from(self).write_fmt(f)
+    pub fn holes(&self) -> Vec<Hole> {
+        table_holes(
+            self.table_width,
+            self.table_height,
+            &self.hole_positions,
+            self.hole_radius,
+            self.hole_radius,
+        )
     }
+
+    /// Problems with the input that don't prevent simulating it but make the result suspicious.
+    /// `side_hole_radius` is the one given with `--side-hole-radius`, if any.
+    pub fn warnings(&self, side_hole_radius: Option<Float>) -> Vec<String> {
+        let mut warnings = vec![];
+        let side_hole_radius = side_hole_radius.unwrap_or(self.hole_radius);
+        let holes = table_holes(
+            self.table_width,
+            self.table_height,
+            &self.hole_positions,
+            self.hole_radius,
+            side_hole_radius,
+        );
+        for (kind, radius) in [
+            (HoleKind::Corner, self.hole_radius),
+            (HoleKind::Side, side_hole_radius),
+        ] {
+            if radius < self.ball_radius && holes.iter().any(|hole| hole.kind == kind) {
+                warnings.push(format!(
+                    "{kind} hole radius ({radius}) is smaller than the ball radius ({}), balls \
+                     can't fit in the {kind} holes",
+                    self.ball_radius
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.restitution) {
+            warnings.push(format!(
+                "restitution ({}) is outside of [0, 1]",
+                self.restitution
+            ));
+        }
+        if self.friction < 0.0 {
+            warnings.push(format!(
+                "friction ({}) is negative, balls will speed up",
+                self.friction
+            ));
+        }
+        let holes = scaled_holes(self);
+        for ball in &self.balls {
+            // Touching a hole is enough to fall in.
+            if is_in_any_hole(ball.position, &holes, self.hole_radius + self.ball_radius) {
+                warnings.push(format!("ball {} starts inside a hole", ball.id));
+            }
+        }
+        warnings
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+    /// Little endian binary, see `IterableFrame::write_binary_to`.
+    Binary,
 }
 
+pub type Frame = simulation::frame::Frame<Ball, Float>;
+
 pub struct IterableFrame<I> {
     pub time: Float,
     pub balls: I,
@@ -58,43 +186,70 @@ impl<'a> From<&'a Frame> for IterableFrame<std::slice::Iter<'a, Ball>> {
     fn from(frame: &'a Frame) -> Self {
         Self {
             time: frame.time,
-            balls: frame.balls.iter(),
+            balls: frame.particles.iter(),
         }
     }
 }
 
 impl<'a, I: ExactSizeIterator<Item = &'a Ball>> IterableFrame<I> {
     pub fn write_fmt<W: std::fmt::Write>(self, f: &mut W) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}\n{}\n", self.balls.len(), self.time))?;
-        for particle in self.balls {
-            f.write_fmt(format_args!(
-                "{} {} {} {} {}\n",
-                particle.id,
-                particle.position.x,
-                particle.position.y,
-                particle.velocity.x,
-                particle.velocity.y,
-            ))?;
-        }
-
-        Ok(())
+        write_frame_fmt(f, self.time, self.balls)
     }
 }
 
 impl<'a, I: ExactSizeIterator<Item = &'a Ball>> IterableFrame<I> {
-    pub fn write_to<W: Write>(self, f: &mut W) -> std::io::Result<()> {
-        f.write_fmt(format_args!("{}\n{}\n", self.balls.len(), self.time))?;
-        for particle in self.balls {
-            f.write_fmt(format_args!(
-                "{} {} {} {} {}\n",
-                particle.id,
-                particle.position.x,
-                particle.position.y,
-                particle.velocity.x,
-                particle.velocity.y,
-            ))?;
+    pub fn write_formatted<W: Write>(self, format: OutputFormat, f: &mut W) -> std::io::Result<()> {
+        match format {
+            OutputFormat::Text => self.write_to(f),
+            OutputFormat::Json => self.write_json_to(f),
+            OutputFormat::Binary => self.write_binary_to(f),
         }
+    }
+
+    pub fn write_to<W: Write>(self, f: &mut W) -> std::io::Result<()> {
+        write_frame(f, self.time, self.balls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::{parse, COASTING_BALL};
+
+    #[test]
+    fn holes_smaller_than_the_balls_are_reported() {
+        let mut input = parse(COASTING_BALL);
+        input.hole_radius = input.ball_radius / 2.0;
+
+        let warnings = input.warnings(Some(input.ball_radius));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("corner hole radius"));
+    }
+
+    #[test]
+    fn side_holes_smaller_than_the_balls_are_reported() {
+        let input = parse(COASTING_BALL);
+
+        assert!(input.warnings(None).is_empty());
+        let warnings = input.warnings(Some(input.ball_radius / 2.0));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("side hole radius"));
+    }
+
+    #[test]
+    fn side_hole_radius_is_ignored_without_side_holes() {
+        let mut input = parse(COASTING_BALL);
+        input.hole_positions.truncate(4);
+
+        assert!(input.warnings(Some(input.ball_radius / 2.0)).is_empty());
+    }
+
+    #[test]
+    fn advance_moves_along_the_velocity() {
+        let ball = parse(COASTING_BALL).balls[0];
+        let advanced = ball.advance(0.5);
 
-        Ok(())
+        assert_eq!(advanced.position, ball.position + ball.velocity * 0.5);
+        assert_eq!(advanced.velocity, ball.velocity);
+        assert_eq!(ball.advance(0.0), ball);
     }
 }
@@ -1,15 +1,43 @@
-use std::io::{BufRead, Lines};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    io::{BufRead, Lines},
+    iter::Enumerate,
+};
+#[cfg(feature = "mmap")]
+use std::{fs::File, path::Path};
 
 use crate::{
     models::{Ball, Frame, InputData},
-    Float,
+    six_pocket_layout, Float,
 };
 use chumsky::{prelude::*, text::newline};
 use cim::particles::ID;
-use itertools::Itertools;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 use nalgebra::Vector2;
 
 pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich<'a, char>>> {
+    input_parser_with_velocity(false)
+}
+
+/// Same format as `input_parser` but with each ball as `id x y speed angle`, with the angle in
+/// radians.
+pub fn polar_input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich<'a, char>>> {
+    input_parser_with_velocity(true)
+}
+
+/// Optional sections that can follow the balls. Each one starts with its name, so any of them can
+/// be left out.
+struct TableOptions {
+    restitution: Option<Float>,
+    friction: Option<Float>,
+    hole_positions: Option<Vec<Vector2<Float>>>,
+}
+
+fn input_parser_with_velocity<'a>(
+    polar: bool,
+) -> impl Parser<'a, &'a str, InputData, extra::Err<Rich<'a, char>>> {
     let digits = text::digits(10);
     let unsigned = digits.map_slice(|s: &str| s.parse::<usize>().unwrap());
 
@@ -22,18 +50,60 @@ pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich
     let ball_data = unsigned
         .then_ignore(just(' '))
         .then(num.separated_by_exactly::<_, _, 4>(just(' ')))
-        .map(|(id, [x, y, vx, vy])| Ball {
+        .map(move |(id, [x, y, v1, v2]): (_, [Float; 4])| Ball {
             id,
             position: Vector2::new(x, y),
-            velocity: Vector2::new(vx, vy),
+            velocity: if polar {
+                Vector2::new(v1 * v2.cos(), v1 * v2.sin())
+            } else {
+                Vector2::new(v1, v2)
+            },
             radius: 0.0,
+            angular_velocity: 0.0,
+        });
+
+    let balls = ball_data.separated_by(newline()).allow_trailing().collect();
+
+    let hole = num
+        .then_ignore(just(' '))
+        .then(num)
+        .try_map(|(x, y): (Float, Float), span| {
+            if (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) {
+                Ok(Vector2::new(x, y))
+            } else {
+                Err(Rich::custom(
+                    span,
+                    format!(
+                        "hole at ({x}, {y}) is outside of the table, hole positions are fractions \
+                         of the table size between 0 and 1"
+                    ),
+                ))
+            }
         });
 
-    let balls = ball_data
-        .separated_by(newline())
-        .at_least(1)
-        .allow_trailing()
-        .collect();
+    // A bare number is also read as the restitution, which is how older inputs wrote it.
+    let restitution = just("restitution ")
+        .ignore_then(num)
+        .or(num)
+        .then_ignore(newline().or_not());
+    let friction = just("friction ")
+        .ignore_then(num)
+        .then_ignore(newline().or_not());
+    let holes = just("holes").ignore_then(newline()).ignore_then(
+        hole.separated_by(newline())
+            .at_least(1)
+            .allow_trailing()
+            .collect(),
+    );
+    let options = restitution
+        .or_not()
+        .then(friction.or_not())
+        .then(holes.or_not())
+        .map(|((restitution, friction), hole_positions)| TableOptions {
+            restitution,
+            friction,
+            hole_positions,
+        });
 
     num.then_ignore(newline())
         .then(num)
@@ -48,11 +118,12 @@ pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich
         .map(|(((((w, h), h_r), r), m), n)| (w, h, h_r, r, m, n))
         .then_ignore(newline())
         .then(balls)
+        .then(options)
         .map(
-            |((table_width, table_height, hole_radius, ball_radius, ball_mass, _), mut balls): (
-                _,
-                Vec<Ball>,
-            )| {
+            |(
+                ((table_width, table_height, hole_radius, ball_radius, ball_mass, _), mut balls),
+                options,
+            ): ((_, Vec<Ball>), TableOptions)| {
                 balls.iter_mut().for_each(|b| b.radius = ball_radius);
                 InputData {
                     table_width,
@@ -60,6 +131,10 @@ pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich
                     hole_radius,
                     ball_radius,
                     ball_mass,
+                    restitution: options.restitution.unwrap_or(1.0),
+                    friction: options.friction.unwrap_or(0.0),
+                    // The six pockets of a pool table.
+                    hole_positions: options.hole_positions.unwrap_or_else(six_pocket_layout),
                     balls,
                 }
             },
@@ -67,59 +142,375 @@ pub fn input_parser<'a>() -> impl Parser<'a, &'a str, InputData, extra::Err<Rich
         .then_ignore(end())
 }
 
-struct Chunks<I> {
-    inner: I,
+/// Error found while reading an output file with `try_output_parser`.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(std::io::Error),
+    /// The line that should start a frame with its ball count isn't a count.
+    BadCount {
+        line: usize,
+        value: String,
+    },
+    /// The file ended in the middle of the frame starting at `line`.
+    ShortFrame {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A value that should be a number isn't one.
+    BadFloat {
+        line: usize,
+        value: String,
+    },
+    /// A ball line doesn't have an id followed by 4 or 5 values.
+    BadBall {
+        line: usize,
+        value: String,
+    },
 }
 
-impl<I: Iterator<Item = String>> Iterator for Chunks<I> {
-    type Item = Vec<I::Item>;
+impl ParseError {
+    /// Whether the frames after this error can still be read. Errors inside a frame only affect
+    /// that frame, but after a bad count the start of the next frame is unknown.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            ParseError::BadFloat { .. } | ParseError::BadBall { .. }
+        )
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut values = vec![];
-        if let Some(count) = self.inner.next().map(|v| v.parse::<usize>().unwrap()) {
-            for _ in 0..count + 1 {
-                if let Some(value) = self.inner.next() {
-                    values.push(value);
-                } else {
-                    return None;
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "error reading the output: {err}"),
+            ParseError::BadCount { line, value } => {
+                write!(f, "line {line}: expected a ball count, found {value:?}")
+            }
+            ParseError::ShortFrame {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: frame should have {expected} lines but the file ends after {found}"
+            ),
+            ParseError::BadFloat { line, value } => {
+                write!(f, "line {line}: expected a number, found {value:?}")
+            }
+            ParseError::BadBall { line, value } => {
+                write!(
+                    f,
+                    "line {line}: expected `id x y vx vy [w]`, found {value:?}"
+                )
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+struct Frames<I> {
+    lines: Enumerate<I>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = std::io::Result<String>>> Frames<I> {
+    fn read_line(&mut self) -> Option<Result<(usize, String), ParseError>> {
+        let (index, line) = self.lines.next()?;
+        Some(line.map(|line| (index + 1, line)).map_err(ParseError::Io))
+    }
+
+    /// Numbered lines of the next frame, starting with its time.
+    fn next_frame_lines(&mut self) -> Option<Result<Vec<(usize, String)>, ParseError>> {
+        let (line, count) = match self.read_line()? {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        let Ok(count) = count.trim().parse::<usize>() else {
+            return Some(Err(ParseError::BadCount { line, value: count }));
+        };
+        let mut lines = Vec::with_capacity(count + 1);
+        for _ in 0..count + 1 {
+            match self.read_line() {
+                Some(Ok(frame_line)) => lines.push(frame_line),
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    return Some(Err(ParseError::ShortFrame {
+                        line,
+                        expected: count + 1,
+                        found: lines.len(),
+                    }))
                 }
             }
-            Some(values)
-        } else {
-            None
         }
+        Some(Ok(lines))
     }
 }
 
-trait CollectedChunks: Iterator + Sized {
-    fn collected_chunks(self) -> Chunks<Self> {
-        Chunks { inner: self }
+impl<I: Iterator<Item = std::io::Result<String>>> Iterator for Frames<I> {
+    type Item = Result<Frame, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_frame_lines()? {
+            Ok(lines) => Some(parse_frame(lines)),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
     }
 }
 
-impl<I: Iterator> CollectedChunks for I {}
+fn parse_float(line: usize, value: &str) -> Result<Float, ParseError> {
+    value.parse().map_err(|_| ParseError::BadFloat {
+        line,
+        value: value.to_owned(),
+    })
+}
 
-pub fn output_parser<B: BufRead>(file: Lines<B>) -> impl Iterator<Item = Frame> {
-    file.map(Result::unwrap).collected_chunks().map(|frame| {
-        let mut frame = frame.into_iter();
-        let time: Float = frame.next().unwrap().parse().unwrap();
-        let balls = frame
-            .map(|line| {
-                let mut values = line.split_whitespace();
-                let id: ID = values.next().unwrap().parse().unwrap();
-                let [x, y, vx, vy]: [Float; 4] = values
-                    .map(|v| v.parse().unwrap())
-                    .collect_vec()
-                    .try_into()
-                    .unwrap();
-                Ball {
-                    id,
-                    position: Vector2::new(x, y),
-                    velocity: Vector2::new(vx, vy),
-                    radius: 0.0,
-                }
+fn parse_frame(lines: Vec<(usize, String)>) -> Result<Frame, ParseError> {
+    let mut lines = lines.into_iter();
+    let (time_line, time) = lines.next().unwrap();
+    let time = parse_float(time_line, time.trim())?;
+    let balls = lines
+        .map(|(line, content)| {
+            let bad_ball = || ParseError::BadBall {
+                line,
+                value: content.clone(),
+            };
+            let mut values = content.split_whitespace();
+            let id: ID = values
+                .next()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(bad_ball)?;
+            let values = values
+                .map(|v| parse_float(line, v))
+                .collect::<Result<Vec<_>, _>>()?;
+            // Output written with `--with-spin` has the angular velocity as an extra column.
+            let (x, y, vx, vy, angular_velocity) = match values[..] {
+                [x, y, vx, vy] => (x, y, vx, vy, 0.0),
+                [x, y, vx, vy, w] => (x, y, vx, vy, w),
+                _ => return Err(bad_ball()),
+            };
+            Ok(Ball {
+                id,
+                position: Vector2::new(x, y),
+                velocity: Vector2::new(vx, vy),
+                radius: 0.0,
+                angular_velocity,
             })
-            .collect_vec();
-        Frame { time, balls }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Frame {
+        time,
+        particles: balls,
+    })
+}
+
+/// Every frame starts with its own ball count, so frames can have different amounts of balls
+/// (pocketed balls stop being written). Don't assume the same set of balls in every frame.
+///
+/// Panics on malformed output, use `try_output_parser` to handle it. A frame cut short at the end
+/// of the file (like when reading the output of a running simulation) is ignored.
+pub fn output_parser<B: BufRead>(file: Lines<B>) -> impl Iterator<Item = Frame> {
+    unwrap_frames(try_output_parser(file))
+}
+
+/// Same as `output_parser` for an output written with `--time-scale`, converting its times back to
+/// seconds.
+pub fn scaled_output_parser<B: BufRead>(
+    file: Lines<B>,
+    time_scale: Float,
+) -> impl Iterator<Item = Frame> {
+    output_parser(file).map(move |frame| Frame {
+        time: frame.time / time_scale,
+        ..frame
     })
 }
+
+/// Same as `output_parser`, returning an error for each malformed frame instead of panicking.
+/// Frames after a recoverable error (see `ParseError::is_recoverable`) are still read, otherwise
+/// the iterator ends after the error.
+pub fn try_output_parser<B: BufRead>(
+    file: Lines<B>,
+) -> impl Iterator<Item = Result<Frame, ParseError>> {
+    try_parse_frames(file)
+}
+
+fn try_parse_frames<I: Iterator<Item = std::io::Result<String>>>(
+    lines: I,
+) -> impl Iterator<Item = Result<Frame, ParseError>> {
+    Frames {
+        lines: lines.enumerate(),
+        done: false,
+    }
+}
+
+fn unwrap_frames(
+    frames: impl Iterator<Item = Result<Frame, ParseError>>,
+) -> impl Iterator<Item = Frame> {
+    frames.map_while(|frame| match frame {
+        Err(ParseError::ShortFrame { .. }) => None,
+        frame => Some(frame.unwrap_or_else(|err| panic!("{err}"))),
+    })
+}
+
+#[cfg(feature = "mmap")]
+struct MmapLines {
+    mmap: Mmap,
+    position: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl Iterator for MmapLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .mmap
+            .get(self.position..)
+            .filter(|rest| !rest.is_empty())?;
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.position += end + 1;
+        Some(
+            String::from_utf8_lossy(&rest[..end])
+                .trim_end_matches('\r')
+                .to_owned(),
+        )
+    }
+}
+
+/// Same as `output_parser` but reading the frames lazily from a memory mapped file, so the file
+/// doesn't need to fit in memory. The file must not be modified while the frames are being read.
+#[cfg(feature = "mmap")]
+pub fn mmap_output_parser<P: AsRef<Path>>(path: P) -> std::io::Result<impl Iterator<Item = Frame>> {
+    let file = File::open(path)?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(unwrap_frames(try_parse_frames(
+        MmapLines { mmap, position: 0 }.map(Ok),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::COASTING_BALL;
+
+    fn parse_input(input: &str) -> InputData {
+        input_parser().parse(input).into_result().unwrap()
+    }
+
+    #[test]
+    fn optional_sections_default() {
+        let input = parse_input(COASTING_BALL);
+        assert_eq!(input.restitution, 1.0);
+        assert_eq!(input.friction, 0.0);
+        assert_eq!(input.hole_positions, six_pocket_layout());
+    }
+
+    #[test]
+    fn bare_restitution() {
+        let input = parse_input(&format!("{COASTING_BALL}0.8\n"));
+        assert_eq!(input.restitution, 0.8);
+        assert_eq!(input.hole_positions, six_pocket_layout());
+    }
+
+    #[test]
+    fn restitution_and_holes() {
+        let input = parse_input(&format!(
+            "{COASTING_BALL}restitution 0.5\nholes\n0 0\n1 1\n"
+        ));
+        assert_eq!(input.restitution, 0.5);
+        assert_eq!(input.friction, 0.0);
+        assert_eq!(
+            input.hole_positions,
+            vec![Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn holes_only() {
+        let input = parse_input(&format!("{COASTING_BALL}holes\n0.5 0\n0.5 1"));
+        assert_eq!(input.restitution, 1.0);
+        assert_eq!(input.friction, 0.0);
+        assert_eq!(
+            input.hole_positions,
+            vec![Vector2::new(0.5, 0.0), Vector2::new(0.5, 1.0)]
+        );
+    }
+
+    #[test]
+    fn every_section() {
+        let input = parse_input(&format!(
+            "{COASTING_BALL}restitution 0.9\nfriction 12.5\nholes\n0 0\n"
+        ));
+        assert_eq!(input.restitution, 0.9);
+        assert_eq!(input.friction, 12.5);
+        assert_eq!(input.hole_positions, vec![Vector2::new(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn hole_outside_of_the_table() {
+        let input = format!("{COASTING_BALL}holes\n1.5 0\n");
+        assert!(input_parser().parse(&input).into_result().is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_parser_matches_the_line_parser() {
+        const OUTPUT: &str = "2\n0\n0 1 2 3 4\n1 5 6 7 8\n1\n0.5\n1 5.5 6 7 8\n";
+        let path = std::env::temp_dir().join(format!("pool-mmap-test-{}", std::process::id()));
+        std::fs::write(&path, OUTPUT).unwrap();
+
+        let mapped = mmap_output_parser(&path).unwrap().collect::<Vec<_>>();
+        std::fs::remove_file(&path).unwrap();
+        let read = output_parser(OUTPUT.as_bytes().lines()).collect::<Vec<_>>();
+
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped.len(), read.len());
+        for (mapped, read) in mapped.iter().zip(&read) {
+            assert_eq!(mapped.time, read.time);
+            assert_eq!(mapped.particles, read.particles);
+        }
+    }
+
+    #[test]
+    fn input_without_balls() {
+        let input = parse_input("224\n112\n11.4\n5.7\n165\n0\n");
+        assert!(input.balls.is_empty());
+    }
+
+    #[test]
+    fn polar_velocities_are_converted() {
+        let input = polar_input_parser()
+            .parse("224\n112\n11.4\n5.7\n165\n2\n0 10.0 20.0 2 0\n1 30.0 40.0 2 1.5707963\n")
+            .into_result()
+            .unwrap();
+
+        let [right, up] = [&input.balls[0], &input.balls[1]].map(|ball| ball.velocity);
+        assert!((right - Vector2::new(2.0, 0.0)).magnitude() < 1e-6);
+        assert!((up - Vector2::new(0.0, 2.0)).magnitude() < 1e-6);
+        assert_eq!(input.balls[1].position, Vector2::new(30.0, 40.0));
+    }
+
+    #[test]
+    fn frames_can_change_their_ball_count() {
+        // Two balls are pocketed, then the table is empty and then two new balls appear.
+        let output = "3\n0\n0 1 1 0 0\n1 2 2 0 0\n2 3 3 0 0\n\
+                      1\n0.5\n2 3 3 0 0\n\
+                      0\n1\n\
+                      2\n1.5\n3 4 4 0 0\n4 5 5 0 0\n";
+
+        let frames: Vec<_> = output_parser(output.as_bytes().lines()).collect();
+        let ids: Vec<Vec<ID>> = frames
+            .iter()
+            .map(|frame| frame.particles.iter().map(|ball| ball.id).collect())
+            .collect();
+        assert_eq!(ids, [vec![0, 1, 2], vec![2], vec![], vec![3, 4]]);
+        assert_eq!(frames[3].time, 1.5);
+    }
+}
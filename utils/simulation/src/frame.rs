@@ -0,0 +1,66 @@
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+/// How a particle is written in the text output, one line per particle in every frame.
+pub trait FrameSerialize {
+    /// Whether frames start with their amount of particles, needed when it can change between
+    /// frames.
+    const WRITE_COUNT: bool = false;
+
+    /// Writes the particle as a single line, without the line break.
+    fn write_line<W: fmt::Write>(&self, f: &mut W) -> fmt::Result;
+}
+
+struct Line<'a, P>(&'a P);
+
+impl<P: FrameSerialize> Display for Line<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_line(f)
+    }
+}
+
+/// Writes the amount of particles (if `P::WRITE_COUNT`), the time and a line for every particle.
+pub fn write_frame<'a, P: FrameSerialize + 'a, T: Display, W: io::Write>(
+    f: &mut W,
+    time: T,
+    particles: impl ExactSizeIterator<Item = &'a P>,
+) -> io::Result<()> {
+    if P::WRITE_COUNT {
+        writeln!(f, "{}", particles.len())?;
+    }
+    writeln!(f, "{time}")?;
+    for particle in particles {
+        writeln!(f, "{}", Line(particle))?;
+    }
+    Ok(())
+}
+
+/// Same as `write_frame` for `fmt::Write`.
+pub fn write_frame_fmt<'a, P: FrameSerialize + 'a, T: Display, W: fmt::Write>(
+    f: &mut W,
+    time: T,
+    particles: impl ExactSizeIterator<Item = &'a P>,
+) -> fmt::Result {
+    if P::WRITE_COUNT {
+        writeln!(f, "{}", particles.len())?;
+    }
+    writeln!(f, "{time}")?;
+    for particle in particles {
+        writeln!(f, "{}", Line(particle))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame<P, T = f64> {
+    pub time: T,
+    pub particles: Vec<P>,
+}
+
+impl<P: FrameSerialize, T: Display> Display for Frame<P, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_frame_fmt(f, &self.time, self.particles.iter())
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    io::{self, Write},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub mod frame;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C stop `drive` after the current step instead of killing the process, so the output
+/// ends on a complete frame and gets flushed. A second Ctrl-C exits right away.
+pub fn stop_on_interrupt() {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            process::exit(130);
+        }
+        eprintln!("Interrupted, stopping after the current step");
+    })
+    .expect("Error setting the Ctrl-C handler.");
+}
+
+/// Simulation that is advanced one step at a time by `drive`.
+pub trait Simulation {
+    type State;
+    type Time: Copy;
+
+    fn state(&self) -> &Self::State;
+    fn time(&self) -> Self::Time;
+
+    /// Advances the simulation by one step, whose length is up to the simulation.
+    fn step(&mut self);
+
+    /// Writes the output for the current state. Called once before the first step and then after
+    /// every step, the simulation decides whether there is anything to write.
+    fn emit<W: Write>(&mut self, writer: &mut W) -> io::Result<()>;
+
+    /// Whether there is nothing left to simulate, regardless of the stop condition.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// Called once after the last step, before the writer is flushed.
+    fn finish<W: Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Steps `simulation` until it is finished, `stop_condition` holds for its state and time or the
+/// process is interrupted (see `stop_on_interrupt`), writing its output to `writer`.
+pub fn drive<S: Simulation, W: Write, F: FnMut(&S::State, S::Time) -> bool>(
+    simulation: &mut S,
+    mut writer: W,
+    mut stop_condition: F,
+) -> io::Result<()> {
+    simulation.emit(&mut writer)?;
+    while !simulation.is_finished()
+        && !INTERRUPTED.load(Ordering::Relaxed)
+        && !stop_condition(simulation.state(), simulation.time())
+    {
+        simulation.step();
+        simulation.emit(&mut writer)?;
+    }
+    simulation.finish(&mut writer)?;
+    writer.flush()
+}